@@ -0,0 +1,177 @@
+//! Asynchronous reading and writing with tokio, enabled by the `tokio`
+//! feature.
+//!
+//! Only the I/O is asynchronous. Parsing, encoding detection and formatting
+//! run the same code as the blocking methods, so both produce identical
+//! results.
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::{ParseError, SfdlError};
+use crate::sfdl::SfdlFile;
+use crate::writer::{self, WriteOptions};
+
+impl SfdlFile {
+    /// Parse a SFDL file from an asynchronous reader.
+    ///
+    /// The asynchronous counterpart of [`SfdlFile::from_reader`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseError::InvalidSfdlDeserialize`] if reading fails or the
+    /// XML cannot be parsed, and the errors of [`SfdlFile::from_bytes`].
+    pub async fn from_async_reader<R>(mut reader: R) -> Result<Self, ParseError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).await.map_err(|err| {
+            ParseError::InvalidSfdlDeserialize(quick_xml::Error::Io(Arc::new(err)).into())
+        })?;
+        Self::from_bytes(&content).map(|(sfdl, _)| sfdl)
+    }
+
+    /// Read a SFDL file from a path without blocking.
+    ///
+    /// The asynchronous counterpart of [`SfdlFile::from_file`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let sfdl = SfdlFile::from_file_async("examples/decrypted.sfdl").await.unwrap();
+    /// assert_eq!(sfdl.description, "MyDescription");
+    /// # });
+    /// ```
+    pub async fn from_file_async<P: AsRef<Path>>(path: P) -> Result<Self, SfdlError> {
+        let content = tokio::fs::read(path).await?;
+        let (sfdl, _) = Self::from_bytes(&content)?;
+
+        Ok(sfdl)
+    }
+
+    /// Serialize this SFDL file into an asynchronous writer.
+    ///
+    /// The writer is flushed afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing fails.
+    pub async fn to_async_writer<W>(&self, writer: W) -> Result<(), SfdlError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.to_async_writer_with(writer, &WriteOptions::default())
+            .await
+    }
+
+    /// Serialize this SFDL file formatted according to `options` into an
+    /// asynchronous writer.
+    ///
+    /// The bytes are the same as those of [`SfdlFile::to_xml_bytes_with`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing fails.
+    pub async fn to_async_writer_with<W>(
+        &self,
+        mut writer: W,
+        options: &WriteOptions,
+    ) -> Result<(), SfdlError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let content = self.to_xml_bytes_with(options)?;
+        writer.write_all(&content).await?;
+        writer.flush().await?;
+
+        Ok(())
+    }
+
+    /// Serialize and write this SFDL file to a file path without blocking.
+    ///
+    /// The asynchronous counterpart of [`SfdlFile::write`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing to the file fails.
+    pub async fn write_async<P: AsRef<Path>>(&self, path: P) -> Result<(), SfdlError> {
+        self.write_async_with(path, &WriteOptions::default()).await
+    }
+
+    /// Serialize and write this SFDL file to a file path, formatted according
+    /// to `options`, without blocking.
+    ///
+    /// The asynchronous counterpart of [`SfdlFile::write_with`], which also
+    /// replaces the file atomically and honors [`WriteOptions::backup`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing to the file fails.
+    pub async fn write_async_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WriteOptions,
+    ) -> Result<(), SfdlError> {
+        let content = self.to_xml_bytes_with(options)?;
+        write_atomic(path.as_ref(), &content, options.backup).await?;
+
+        Ok(())
+    }
+}
+
+/// The asynchronous counterpart of `writer::write_atomic`.
+async fn write_atomic(path: &Path, content: &[u8], backup: bool) -> io::Result<()> {
+    let temp = writer::temp_path(path);
+    let result = async {
+        write_temp(&temp, path, content).await?;
+        if backup {
+            match fs::copy(path, writer::backup_path(path)).await {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        fs::rename(&temp, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+    result?;
+
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(dir) = fs::File::open(parent).await {
+            let _ = dir.sync_all().await;
+        }
+    }
+    Ok(())
+}
+
+async fn write_temp(temp: &Path, path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp)
+        .await?;
+    if let Ok(metadata) = fs::metadata(path).await {
+        file.set_permissions(metadata.permissions()).await?;
+    }
+    file.write_all(content).await?;
+    file.sync_all().await
+}
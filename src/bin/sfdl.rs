@@ -0,0 +1,421 @@
+//! Command line interface for inspecting, encrypting and decrypting SFDL files.
+//!
+//! Build or install it with the `cli` feature enabled:
+//!
+//! ```bash
+//! cargo install sfdl --features cli
+//! ```
+//!
+//! # Usage
+//!
+//! ```bash
+//! sfdl info container.sfdl
+//! sfdl encrypt -p "my-password" -o encrypted.sfdl decrypted.sfdl
+//! sfdl decrypt -p "my-password" encrypted.sfdl
+//! sfdl validate container.sfdl
+//! sfdl convert --migrate 6 container.sfdl -o normalized.sfdl
+//! sfdl convert --to json container.sfdl -o container.json
+//! sfdl rekey --old-password "old" --new-password "new" encrypted.sfdl
+//! ```
+//!
+//! `info` masks the server host unless `--show-secrets` is given; usernames
+//! and passwords are never printed.
+//!
+//! Passwords that are not given on the command line are read from the
+//! `SFDL_PASSWORD`, `SFDL_OLD_PASSWORD` and `SFDL_NEW_PASSWORD` environment
+//! variables, or prompted for on the terminal without echo. If stdin is not
+//! a terminal, a password is read from its first line instead.
+//!
+//! Containers are written indented, with an XML declaration and a UTF-8 BOM,
+//! the way SFDL.NET writes them. Output files are replaced atomically, and
+//! `--backup` keeps the previous one as `<output>.bak`.
+//!
+//! `validate` prints every schema violation and structural problem it finds
+//! and exits with 8 if one of them is an error. Input that is not an SFDL
+//! document at all, such as malformed XML, still exits with 3 after its
+//! diagnostics are printed.
+//!
+//! `convert` also translates between SFDL and JSON, YAML or TOML. The input
+//! format is taken from the file extension unless `--from` is given.
+//!
+//! # Exit codes
+//!
+//! | Code | Meaning                                               |
+//! |------|-------------------------------------------------------|
+//! | 0    | Success                                               |
+//! | 1    | I/O error                                             |
+//! | 2    | Invalid command line usage                            |
+//! | 3    | The container could not be parsed or serialized       |
+//! | 4    | Decryption failed, usually due to a wrong password    |
+//! | 6    | The container is already encrypted                    |
+//! | 7    | The container is not encrypted                        |
+//! | 8    | Validation found at least one error                   |
+//! | 9    | The container cannot be migrated to the given version |
+
+use std::error::Error;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use sfdl::{writer, Diagnostic, DiagnosticCode, Severity, SfdlError, SfdlFile, WriteOptions};
+
+/// Exit code used when `validate` reports at least one error.
+const EXIT_INVALID: u8 = 8;
+
+/// Inspect, encrypt and decrypt SFDL container files.
+#[derive(Parser, Debug)]
+#[command(name = "sfdl", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a summary of a container.
+    Info {
+        /// Input SFDL file.
+        input: PathBuf,
+        /// Password used to decrypt an encrypted container before printing it.
+        #[arg(short, long, env = "SFDL_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+        /// Print the server host instead of masking it.
+        #[arg(long)]
+        show_secrets: bool,
+    },
+    /// Encrypt a container.
+    Encrypt(CryptArgs),
+    /// Decrypt a container.
+    Decrypt(CryptArgs),
+    /// Check a container against the schema and for structural problems.
+    ///
+    /// Every problem found is printed. Exits with 8 if one of them is an
+    /// error, or with 3 if the input is not an SFDL document at all.
+    Validate {
+        /// Input SFDL file.
+        input: PathBuf,
+        /// Password used to decrypt an encrypted container before validating it.
+        #[arg(short, long, env = "SFDL_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Re-serialize a container, optionally migrating its file version or
+    /// converting it to another format.
+    Convert {
+        /// Input file.
+        input: PathBuf,
+        /// Output file path (defaults to stdout).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Migrate the container to this `SFDLFileVersion` before writing it.
+        #[arg(long, value_name = "VERSION")]
+        migrate: Option<u16>,
+        /// Format of the input file (defaults to its file extension).
+        #[arg(long, value_enum)]
+        from: Option<Format>,
+        /// Format to write.
+        #[arg(long, value_enum, default_value_t = Format::Sfdl)]
+        to: Format,
+    },
+    /// Change the password of an encrypted container.
+    Rekey {
+        /// Input SFDL file.
+        input: PathBuf,
+        /// Output file path (defaults to overwriting the input file).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Current password (prompted for if not provided).
+        #[arg(long, env = "SFDL_OLD_PASSWORD", hide_env_values = true)]
+        old_password: Option<String>,
+        /// New password (prompted for if not provided).
+        #[arg(long, env = "SFDL_NEW_PASSWORD", hide_env_values = true)]
+        new_password: Option<String>,
+        /// Keep the replaced output file as `<output>.bak`.
+        #[arg(long)]
+        backup: bool,
+    },
+}
+
+/// File formats `convert` reads and writes.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// SFDL XML.
+    Sfdl,
+    /// JSON.
+    Json,
+    /// YAML.
+    Yaml,
+    /// TOML.
+    Toml,
+}
+
+impl Format {
+    /// Guesses the format from the extension of `path`, defaulting to SFDL.
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Self::Yaml
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::Toml,
+            _ => Self::Sfdl,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct CryptArgs {
+    /// Input SFDL file.
+    input: PathBuf,
+    /// Output file path (defaults to overwriting the input file).
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Password to encrypt/decrypt with (prompted for if not provided).
+    #[arg(short, long, env = "SFDL_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    /// Keep the replaced output file as `<output>.bak`.
+    #[arg(long)]
+    backup: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli.command) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", error_chain(&err));
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
+
+/// Maps an error to the documented process exit code.
+fn exit_code(err: &SfdlError) -> u8 {
+    match err {
+        SfdlError::Io(_) => 1,
+        SfdlError::Parse(_) | SfdlError::Format(_) => 3,
+        SfdlError::Decrypt(_) => 4,
+        SfdlError::AlreadyEncrypted => 6,
+        SfdlError::NotEncrypted => 7,
+        SfdlError::Migrate(_) => 9,
+    }
+}
+
+/// Formats an error together with all of its sources.
+fn error_chain(err: &dyn Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+fn run(command: Command) -> Result<ExitCode, SfdlError> {
+    match command {
+        Command::Info {
+            input,
+            password,
+            show_secrets,
+        } => {
+            let mut sfdl = SfdlFile::from_file(&input)?;
+            if sfdl.encrypted {
+                if let Some(password) = password {
+                    sfdl.decrypt(&password)?;
+                }
+            }
+            print_info(&sfdl, show_secrets);
+        }
+        Command::Encrypt(args) => {
+            let mut sfdl = SfdlFile::from_file(&args.input)?;
+            let password = password_or_prompt(args.password, "Enter password: ")?;
+            sfdl.encrypt(&password)?;
+            let output = args.output.unwrap_or(args.input.clone());
+            write_file(&sfdl, &output, args.backup)?;
+            println!("Encrypted {} -> {}", args.input.display(), output.display());
+        }
+        Command::Decrypt(args) => {
+            let mut sfdl = SfdlFile::from_file(&args.input)?;
+            let password = password_or_prompt(args.password, "Enter password: ")?;
+            sfdl.decrypt(&password)?;
+            let output = args.output.unwrap_or(args.input.clone());
+            write_file(&sfdl, &output, args.backup)?;
+            println!("Decrypted {} -> {}", args.input.display(), output.display());
+        }
+        Command::Validate { input, password } => {
+            let content = std::fs::read(&input)?;
+            let diagnostics = SfdlFile::validate_schema(&content);
+            let schema_valid = report(&diagnostics);
+            if !schema_valid && !diagnostics.iter().any(is_document_error) {
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+
+            // Input that is not an SFDL document at all fails as a parse error.
+            let (mut sfdl, _) = SfdlFile::from_bytes(&content)?;
+            if !schema_valid {
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+            if let Some(password) = password.filter(|_| sfdl.encrypted) {
+                sfdl.decrypt(&password)?;
+            }
+            if !report(&sfdl.validate()) {
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+            println!("{}: valid", input.display());
+        }
+        Command::Convert {
+            input,
+            output,
+            migrate,
+            from,
+            to,
+        } => {
+            let mut sfdl = read_as(&input, from.unwrap_or_else(|| Format::of(&input)))?;
+            if let Some(version) = migrate {
+                sfdl.migrate_to(version)?;
+            }
+            match to {
+                Format::Sfdl => write_or_print(&sfdl, output.as_deref())?,
+                Format::Json => print_or_save(&sfdl.to_json()?, output.as_deref())?,
+                Format::Yaml => print_or_save(&sfdl.to_yaml()?, output.as_deref())?,
+                Format::Toml => print_or_save(&sfdl.to_toml()?, output.as_deref())?,
+            }
+        }
+        Command::Rekey {
+            input,
+            output,
+            old_password,
+            new_password,
+            backup,
+        } => {
+            let mut sfdl = SfdlFile::from_file(&input)?;
+            let old_password = password_or_prompt(old_password, "Enter current password: ")?;
+            let new_password = password_or_prompt(new_password, "Enter new password: ")?;
+            sfdl.rekey(&old_password, &new_password)?;
+            let output = output.unwrap_or(input.clone());
+            write_file(&sfdl, &output, backup)?;
+            println!("Rekeyed {} -> {}", input.display(), output.display());
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints `diagnostics` and returns `false` if any of them is an error.
+fn report(diagnostics: &[Diagnostic]) -> bool {
+    for diagnostic in diagnostics {
+        println!("{diagnostic}");
+    }
+    !diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+/// Whether `diagnostic` is an error about the document as a whole, malformed
+/// XML or a missing `SFDLFile` root, rather than about its content.
+fn is_document_error(diagnostic: &Diagnostic) -> bool {
+    match diagnostic.code {
+        DiagnosticCode::MalformedXml => true,
+        DiagnosticCode::MissingElement => diagnostic.path == "/SFDLFile",
+        _ => false,
+    }
+}
+
+fn print_info(sfdl: &SfdlFile, show_secrets: bool) {
+    println!("Description:  {}", sfdl.description);
+    println!("Uploader:     {}", sfdl.uploader);
+    println!("Version:      {}", sfdl.sfdlfile_version);
+    println!(
+        "Encrypted:    {}",
+        if sfdl.encrypted { "yes" } else { "no" }
+    );
+    let host = if show_secrets {
+        sfdl.connection_info.host.as_str()
+    } else {
+        "[redacted]"
+    };
+    println!("Host:         {host}:{}", sfdl.connection_info.port);
+    println!("Threads:      {}", sfdl.max_download_threads);
+    println!("Packages:     {}", sfdl.packages.len());
+
+    for package in sfdl.packages.iter() {
+        let files = package
+            .file_list
+            .as_ref()
+            .map_or(&[][..], |list| &list.file_info);
+        println!(
+            "  - {} (bulk folders: {}, files: {}, size: {} bytes)",
+            package.package_name,
+            package.bulk_folder_list.bulk_folder.len(),
+            files.len(),
+            package.total_size()
+        );
+    }
+}
+
+/// Writes `sfdl` in the layout SFDL.NET produces, replacing `path`
+/// atomically.
+fn write_file(sfdl: &SfdlFile, path: &Path, backup: bool) -> Result<(), SfdlError> {
+    sfdl.write_with(path, &WriteOptions::dotnet().backup(backup))
+}
+
+fn write_or_print(sfdl: &SfdlFile, output: Option<&Path>) -> Result<(), SfdlError> {
+    match output {
+        Some(path) => write_file(sfdl, path, false),
+        None => {
+            let content = sfdl.to_xml_string_with(&WriteOptions::dotnet().bom(false))?;
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(content.as_bytes())?;
+            stdout.write_all(b"\n")?;
+            Ok(())
+        }
+    }
+}
+
+/// Reads the container at `path`, stored in `format`.
+fn read_as(path: &Path, format: Format) -> Result<SfdlFile, SfdlError> {
+    Ok(match format {
+        Format::Sfdl => SfdlFile::from_file(path)?,
+        Format::Json => SfdlFile::from_json(&std::fs::read_to_string(path)?)?,
+        Format::Yaml => SfdlFile::from_yaml(&std::fs::read_to_string(path)?)?,
+        Format::Toml => SfdlFile::from_toml(&std::fs::read_to_string(path)?)?,
+    })
+}
+
+/// Writes `content` to `output` atomically, or to stdout if no path is given.
+fn print_or_save(content: &str, output: Option<&Path>) -> Result<(), SfdlError> {
+    match output {
+        Some(path) => writer::write_atomic(path, content.as_bytes(), false)?,
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(content.as_bytes())?;
+            if !content.ends_with('\n') {
+                stdout.write_all(b"\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the given password, prompts for one on the terminal without
+/// echoing it, or reads one line from stdin if it is not a terminal.
+fn password_or_prompt(password: Option<String>, prompt: &str) -> Result<String, SfdlError> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    if io::stdin().is_terminal() {
+        return Ok(rpassword::prompt_password(prompt)?);
+    }
+
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input
+        .trim_end_matches('\n')
+        .trim_end_matches('\r')
+        .to_string())
+}
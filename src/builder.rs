@@ -0,0 +1,486 @@
+//! Typed builders for constructing SFDL containers in code.
+//!
+//! Filling in the public model fields by hand is error prone: every
+//! `BulkFolder.PackageName` and `FileInfo.PackageName` has to repeat the name
+//! of its parent package, and [`SfdlFile::default`] already contains one empty
+//! package. The builders in this module start from an empty container, keep
+//! the package names in sync automatically and check required data in
+//! `build()`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sfdl::{ConnectionInfo, SfdlFile, SfdlPackage};
+//!
+//! let sfdl = SfdlFile::builder()
+//!     .description("Holiday pictures")
+//!     .uploader("me")
+//!     .connection_info(
+//!         ConnectionInfo::builder()
+//!             .host("ftp.example.com")
+//!             .credentials("user", "secret")
+//!             .build()
+//!             .unwrap(),
+//!     )
+//!     .package(
+//!         SfdlPackage::builder("Pictures")
+//!             .bulk_folder("/pictures/2024")
+//!             .build()
+//!             .unwrap(),
+//!     )
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(sfdl.packages[0].bulk_folder_list.bulk_folder[0].package_name, "Pictures");
+//! ```
+
+use crate::error::BuildError;
+use crate::secret::SecretString;
+use crate::sfdl::{
+    BulkFolder, BulkFolderList, CharacterEncoding, ConnectionInfo, DataConnectionType, DataType,
+    EncryptionMode, FileInfo, FileList, ListMethod, Packages, SfdlFile, SfdlPackage,
+};
+
+/// Builder for [`SfdlFile`].
+///
+/// Created with [`SfdlFile::builder`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct SfdlFileBuilder {
+    description: String,
+    uploader: String,
+    sfdlfile_version: u16,
+    max_download_threads: u16,
+    connection_info: Option<ConnectionInfo>,
+    packages: Vec<SfdlPackage>,
+}
+
+impl SfdlFileBuilder {
+    fn new() -> Self {
+        let defaults = SfdlFile::default();
+        Self {
+            description: String::new(),
+            uploader: String::new(),
+            sfdlfile_version: defaults.sfdlfile_version,
+            max_download_threads: defaults.max_download_threads,
+            connection_info: None,
+            packages: Vec::new(),
+        }
+    }
+
+    /// Set the description of the container.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Set the uploader of the container.
+    pub fn uploader(mut self, uploader: impl Into<String>) -> Self {
+        self.uploader = uploader.into();
+        self
+    }
+
+    /// Set the SFDL file format version. Defaults to `6`.
+    pub fn sfdlfile_version(mut self, version: u16) -> Self {
+        self.sfdlfile_version = version;
+        self
+    }
+
+    /// Set the maximum number of concurrent download threads. Defaults to `3`.
+    pub fn max_download_threads(mut self, threads: u16) -> Self {
+        self.max_download_threads = threads;
+        self
+    }
+
+    /// Set the connection settings. Required.
+    pub fn connection_info(mut self, connection_info: ConnectionInfo) -> Self {
+        self.connection_info = Some(connection_info);
+        self
+    }
+
+    /// Append a package.
+    pub fn package(mut self, package: SfdlPackage) -> Self {
+        self.packages.push(package);
+        self
+    }
+
+    /// Append several packages.
+    pub fn packages(mut self, packages: impl IntoIterator<Item = SfdlPackage>) -> Self {
+        self.packages.extend(packages);
+        self
+    }
+
+    /// Build the container.
+    ///
+    /// The container is always built in plaintext, call
+    /// [`SfdlFile::encrypt`] afterwards to encrypt it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BuildError::MissingConnectionInfo`] if no connection settings
+    /// were given, or [`BuildError::NoPackages`] if no package was added.
+    pub fn build(self) -> Result<SfdlFile, BuildError> {
+        let connection_info = self
+            .connection_info
+            .ok_or(BuildError::MissingConnectionInfo)?;
+        if self.packages.is_empty() {
+            return Err(BuildError::NoPackages);
+        }
+
+        Ok(SfdlFile {
+            description: self.description,
+            uploader: self.uploader,
+            sfdlfile_version: self.sfdlfile_version,
+            encrypted: false,
+            connection_info,
+            packages: Packages {
+                package: self.packages,
+            },
+            max_download_threads: self.max_download_threads,
+            ..SfdlFile::default()
+        })
+    }
+}
+
+/// Builder for [`ConnectionInfo`].
+///
+/// Created with [`ConnectionInfo::builder`]. Unset values fall back to
+/// [`ConnectionInfo::default`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct ConnectionInfoBuilder {
+    host: Option<String>,
+    info: ConnectionInfo,
+}
+
+impl ConnectionInfoBuilder {
+    fn new() -> Self {
+        Self {
+            host: None,
+            info: ConnectionInfo::default(),
+        }
+    }
+
+    /// Set the server hostname or IP address. Required.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Set the server port. Defaults to `21`.
+    pub fn port(mut self, port: u16) -> Self {
+        self.info.port = port;
+        self
+    }
+
+    /// Set username and password and mark authentication as required.
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.info.username = SecretString::new(username);
+        self.info.password = SecretString::new(password);
+        self.info.auth_required = true;
+        self
+    }
+
+    /// Set the FTP data connection mode.
+    pub fn data_connection_type(mut self, data_connection_type: DataConnectionType) -> Self {
+        self.info.data_connection_type = data_connection_type;
+        self
+    }
+
+    /// Set the transfer data type.
+    pub fn data_type(mut self, data_type: DataType) -> Self {
+        self.info.data_type = data_type;
+        self
+    }
+
+    /// Set the character encoding used by the server.
+    pub fn character_encoding(mut self, character_encoding: CharacterEncoding) -> Self {
+        self.info.character_encoding = character_encoding;
+        self
+    }
+
+    /// Set the connection encryption mode.
+    pub fn encryption_mode(mut self, encryption_mode: EncryptionMode) -> Self {
+        self.info.encryption_mode = encryption_mode;
+        self
+    }
+
+    /// Set the method used for listing directory contents.
+    pub fn list_method(mut self, list_method: ListMethod) -> Self {
+        self.info.list_method = list_method;
+        self
+    }
+
+    /// Set the default path on the server. Defaults to `/`.
+    pub fn default_path(mut self, default_path: impl Into<String>) -> Self {
+        self.info.default_path = default_path.into();
+        self
+    }
+
+    /// Set whether to force a single connection.
+    pub fn force_single_connection(mut self, force_single_connection: bool) -> Self {
+        self.info.force_single_connection = force_single_connection;
+        self
+    }
+
+    /// Set whether stale data detection is enabled.
+    pub fn data_stale_detection(mut self, data_stale_detection: bool) -> Self {
+        self.info.data_stale_detection = data_stale_detection;
+        self
+    }
+
+    /// Set whether special server compatibility mode is enabled.
+    pub fn special_server_mode(mut self, special_server_mode: bool) -> Self {
+        self.info.special_server_mode = special_server_mode;
+        self
+    }
+
+    /// Build the connection settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BuildError::MissingHost`] if no host or an empty host was
+    /// given, or [`BuildError::InvalidPort`] if the port is `0`.
+    pub fn build(self) -> Result<ConnectionInfo, BuildError> {
+        let host = self
+            .host
+            .filter(|host| !host.is_empty())
+            .ok_or(BuildError::MissingHost)?;
+        if self.info.port == 0 {
+            return Err(BuildError::InvalidPort);
+        }
+
+        Ok(ConnectionInfo { host, ..self.info })
+    }
+}
+
+/// Builder for [`SfdlPackage`].
+///
+/// Created with [`SfdlPackage::builder`]. The package name is copied into
+/// every bulk folder and file entry, so they always agree with the package.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct PackageBuilder {
+    package_name: String,
+    bulk_folder_mode: Option<bool>,
+    bulk_folders: Vec<BulkFolder>,
+    files: Vec<FileInfo>,
+}
+
+impl PackageBuilder {
+    fn new(package_name: String) -> Self {
+        Self {
+            package_name,
+            bulk_folder_mode: None,
+            bulk_folders: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Append a bulk folder.
+    pub fn bulk_folder(mut self, bulk_folder_path: impl Into<String>) -> Self {
+        self.bulk_folders.push(BulkFolder {
+            bulk_folder_path: bulk_folder_path.into(),
+            package_name: String::new(),
+        });
+        self
+    }
+
+    /// Append a file entry.
+    ///
+    /// [`FileInfo::package_name`] is overwritten with the package name.
+    pub fn file(mut self, file: FileInfo) -> Self {
+        self.files.push(file);
+        self
+    }
+
+    /// Append several file entries.
+    ///
+    /// [`FileInfo::package_name`] is overwritten with the package name.
+    pub fn files(mut self, files: impl IntoIterator<Item = FileInfo>) -> Self {
+        self.files.extend(files);
+        self
+    }
+
+    /// Override bulk-folder mode.
+    ///
+    /// By default the package uses bulk-folder mode unless it only contains
+    /// file entries.
+    pub fn bulk_folder_mode(mut self, bulk_folder_mode: bool) -> Self {
+        self.bulk_folder_mode = Some(bulk_folder_mode);
+        self
+    }
+
+    /// Build the package.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BuildError::EmptyPackage`] if the package has neither bulk
+    /// folders nor file entries.
+    pub fn build(self) -> Result<SfdlPackage, BuildError> {
+        if self.bulk_folders.is_empty() && self.files.is_empty() {
+            return Err(BuildError::EmptyPackage {
+                package_name: self.package_name,
+            });
+        }
+
+        let package_name = self.package_name;
+        let bulk_folder_mode = self
+            .bulk_folder_mode
+            .unwrap_or(!self.bulk_folders.is_empty() || self.files.is_empty());
+
+        let bulk_folder = self
+            .bulk_folders
+            .into_iter()
+            .map(|folder| BulkFolder {
+                package_name: package_name.clone(),
+                ..folder
+            })
+            .collect();
+        let file_list = (!self.files.is_empty()).then(|| FileList {
+            file_info: self
+                .files
+                .into_iter()
+                .map(|file| FileInfo {
+                    package_name: package_name.clone(),
+                    ..file
+                })
+                .collect(),
+        });
+
+        Ok(SfdlPackage {
+            package_name,
+            bulk_folder_mode,
+            bulk_folder_list: BulkFolderList { bulk_folder },
+            file_list,
+            ..SfdlPackage::default()
+        })
+    }
+}
+
+impl SfdlFile {
+    /// Start building a new container.
+    ///
+    /// See [`SfdlFileBuilder`].
+    pub fn builder() -> SfdlFileBuilder {
+        SfdlFileBuilder::new()
+    }
+}
+
+impl ConnectionInfo {
+    /// Start building new connection settings.
+    ///
+    /// See [`ConnectionInfoBuilder`].
+    pub fn builder() -> ConnectionInfoBuilder {
+        ConnectionInfoBuilder::new()
+    }
+}
+
+impl SfdlPackage {
+    /// Start building a new package with the given name.
+    ///
+    /// See [`PackageBuilder`].
+    pub fn builder(package_name: impl Into<String>) -> PackageBuilder {
+        PackageBuilder::new(package_name.into())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn connection_info() -> ConnectionInfo {
+        ConnectionInfo::builder()
+            .host("ftp.example.com")
+            .credentials("listuser", "listpass")
+            .character_encoding(CharacterEncoding::UTF8)
+            .default_path("/default/list")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_build_matches_sample() {
+        let file = |name: &str, size| FileInfo {
+            file_name: name.to_string(),
+            directory_root: "/root".to_string(),
+            directory_path: "/root/files".to_string(),
+            file_full_path: format!("/root/files/{name}"),
+            file_size: size,
+            file_hash_type: "MD5".to_string(),
+            ..Default::default()
+        };
+
+        let sfdl = SfdlFile::builder()
+            .description("File list description")
+            .uploader("File list uploader")
+            .connection_info(connection_info())
+            .package(
+                SfdlPackage::builder("FileListPackage")
+                    .file(FileInfo {
+                        file_hash: "d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                        ..file("readme.txt", 42)
+                    })
+                    .file(FileInfo {
+                        file_hash: "abc123def456".to_string(),
+                        ..file("data.bin", 1024)
+                    })
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let expected = SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap();
+        assert_eq!(sfdl, expected);
+    }
+
+    #[test]
+    fn test_package_names_are_synced() {
+        let package = SfdlPackage::builder("Pkg")
+            .bulk_folder("/a")
+            .file(FileInfo {
+                package_name: "Other".to_string(),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        assert!(package.bulk_folder_mode);
+        assert_eq!(package.bulk_folder_list.bulk_folder[0].package_name, "Pkg");
+        assert_eq!(package.file_list.unwrap().file_info[0].package_name, "Pkg");
+    }
+
+    #[test]
+    fn test_build_errors() {
+        assert_eq!(
+            ConnectionInfo::builder().build(),
+            Err(BuildError::MissingHost)
+        );
+        assert_eq!(
+            ConnectionInfo::builder().host("").build(),
+            Err(BuildError::MissingHost)
+        );
+        assert_eq!(
+            ConnectionInfo::builder().host("host").port(0).build(),
+            Err(BuildError::InvalidPort)
+        );
+        assert_eq!(
+            SfdlPackage::builder("Pkg").build(),
+            Err(BuildError::EmptyPackage {
+                package_name: "Pkg".to_string()
+            })
+        );
+        assert_eq!(
+            SfdlFile::builder().build(),
+            Err(BuildError::MissingConnectionInfo)
+        );
+        assert_eq!(
+            SfdlFile::builder()
+                .connection_info(connection_info())
+                .build(),
+            Err(BuildError::NoPackages)
+        );
+    }
+}
@@ -16,7 +16,7 @@
 //! - `ConnectionInfo.Host`
 //! - `ConnectionInfo.Password`
 //! - `ConnectionInfo.Username`
-//! - `ConnectionInfo.DefaultPath`
+//! - `ConnectionInfo.DefaultPath`, depending on the [`EncryptionProfile`]
 //! - For every `SFDLPackage`:
 //!   - `SFDLPackage.Packagename`
 //!   - For every `BulkFolder`:
@@ -37,146 +37,861 @@
 //! attacks. These limitations are inherent to the format, not a bug in this
 //! crate.
 //!
+//! # Single values
+//!
+//! [`SfdlKey`] encrypts, decrypts and verifies individual values, for example
+//! to look at one field of a container without parsing it. Payloads can be
+//! given as `&str` or `&[u8]`. [`encrypt_value`] and [`decrypt_value`] derive
+//! the key from a password for one-off use.
+//!
+//! ```rust
+//! use sfdl::crypto::{self, SfdlKey};
+//!
+//! let key = SfdlKey::from_password("S3cr3tP4ssw0rd!");
+//! let encrypted = key.encrypt("ftp.example.com");
+//! assert_eq!(key.decrypt(&encrypted).unwrap(), "ftp.example.com");
+//! assert!(key.verify(&encrypted).is_ok());
+//!
+//! let encrypted = crypto::encrypt_value([0xFF, 0x00], "pw");
+//! assert_eq!(crypto::decrypt_value_bytes(&encrypted, "pw").unwrap(), [0xFF, 0x00]);
+//! assert!(crypto::decrypt_value(&encrypted, "pw").is_err());
+//! ```
+//!
 //! # Atomicity
 //!
-//! `encrypt_sfdl` and `decrypt_sfdl` are pure functions that return a new
-//! struct. The public [`SfdlFile::encrypt`](crate::SfdlFile::encrypt)
-//! and [`SfdlFile::decrypt`](crate::SfdlFile::decrypt) methods only
+//! [`SfdlFile::encrypt`](crate::SfdlFile::encrypt) and
+//! [`SfdlFile::decrypt`](crate::SfdlFile::decrypt) work on a copy and only
 //! replace the caller's value after the whole operation succeeds, so a
 //! malformed field or wrong password never leaves the object half changed.
 
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
 use aes::Aes128;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 use cbc::{Decryptor, Encryptor};
 use cipher::block_padding::Pkcs7;
-use cipher::{BlockModeDecrypt, BlockModeEncrypt, KeyIvInit};
-use rand::prelude::*;
+use cipher::{BlockModeDecrypt, BlockModeEncrypt, InnerIvInit, KeyInit};
 
 use crate::error::DecryptError;
-use crate::sfdl::SfdlFile;
+use crate::iv::{IvSource, OsIvSource};
+use crate::secret::{replace, wipe, SecretString};
+use crate::sfdl::{ConnectionInfo, FileInfo, SfdlFile, SfdlPackage};
 
 const AES_BLOCK_SIZE: usize = 16;
 
-/// Decrypts a single value using the SFDL AES-128-CBC primitive.
-pub fn decrypt_value(encrypted_data: &str, password: &str) -> Result<String, DecryptError> {
-    let digest = md5::compute(password.as_bytes());
-    let key: &[u8; AES_BLOCK_SIZE] = &digest.0;
+/// An AES-128 key derived from an SFDL password.
+///
+/// Deriving the key hashes the password with MD5 and expands the AES key
+/// schedule. Both happen once in [`SfdlKey::from_password`], so a key can be
+/// reused for every field of a container, or cached across containers from
+/// the same uploader.
+///
+/// The [`Debug`] output does not include the key. With the `zeroize` feature
+/// the key bytes and the expanded key schedule are wiped on drop.
+///
+/// # Example
+///
+/// ```rust
+/// # use sfdl::{SfdlFile, SfdlKey};
+/// let key = SfdlKey::from_password("S3cr3tP4ssw0rd!");
+///
+/// let mut sfdl = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+/// sfdl.decrypt_with_key(&key).unwrap();
+/// sfdl.encrypt_with_key(&key).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SfdlKey {
+    bytes: [u8; AES_BLOCK_SIZE],
+    cipher: Aes128,
+}
+
+impl SfdlKey {
+    /// Derives the key for `password`: the MD5 digest of its UTF-8 bytes.
+    ///
+    /// Empty passwords are accepted to match the reference implementation,
+    /// even though they result in weak encryption.
+    #[must_use]
+    pub fn from_password(password: &str) -> Self {
+        let mut digest = md5::compute(password.as_bytes()).0;
+        let key = Self::from_bytes(digest);
+        wipe(&mut digest);
+        key
+    }
+
+    /// Uses `bytes` as the AES-128 key directly.
+    #[must_use]
+    pub fn from_bytes(bytes: [u8; AES_BLOCK_SIZE]) -> Self {
+        Self {
+            bytes,
+            cipher: Aes128::new(&bytes.into()),
+        }
+    }
+
+    /// The raw AES-128 key.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; AES_BLOCK_SIZE] {
+        &self.bytes
+    }
+
+    fn encryptor(&self, iv: &[u8; AES_BLOCK_SIZE]) -> Encryptor<Aes128> {
+        Encryptor::inner_iv_init(self.cipher.clone(), iv.into())
+    }
+
+    fn decryptor(&self, iv: &[u8; AES_BLOCK_SIZE]) -> Decryptor<Aes128> {
+        Decryptor::inner_iv_init(self.cipher.clone(), iv.into())
+    }
+}
+
+impl fmt::Debug for SfdlKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SfdlKey").finish_non_exhaustive()
+    }
+}
 
-    let decoded = BASE64_STANDARD.decode(encrypted_data)?;
+impl Drop for SfdlKey {
+    fn drop(&mut self) {
+        wipe(&mut self.bytes);
+    }
+}
+
+impl PartialEq for SfdlKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for SfdlKey {}
+
+impl SfdlKey {
+    /// Encrypts a single value with a secure random IV.
+    ///
+    /// Returns the base64 encoded IV and ciphertext, as stored in SFDL files.
+    #[must_use]
+    pub fn encrypt(&self, plaintext: impl AsRef<[u8]>) -> String {
+        self.encrypt_with(plaintext, &mut OsIvSource)
+    }
+
+    /// Encrypts a single value with the next IV from `ivs`.
+    #[must_use]
+    pub fn encrypt_with(&self, plaintext: impl AsRef<[u8]>, ivs: &mut impl IvSource) -> String {
+        let iv = ivs.next_iv();
+        let encrypted_data = self
+            .encryptor(&iv)
+            .encrypt_padded_vec::<Pkcs7>(plaintext.as_ref());
+        let encrypted_data = [iv.to_vec(), encrypted_data].concat();
+        BASE64_STANDARD.encode(&encrypted_data)
+    }
+
+    /// Decrypts a single base64 encoded value into a string.
+    ///
+    /// Leading and trailing whitespace around the base64 text is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DecryptError::InvalidData`] if the value is not base64,
+    /// [`DecryptError::InvalidCiphertextLength`] if it is not an IV followed by
+    /// at least one whole block, [`DecryptError::InvalidPassword`] if the
+    /// padding is wrong and
+    /// [`DecryptError::InvalidEncoding`] if the plaintext is not UTF-8.
+    pub fn decrypt(&self, ciphertext: impl AsRef<[u8]>) -> Result<String, DecryptError> {
+        Ok(String::from_utf8(self.decrypt_bytes(ciphertext)?)?)
+    }
+
+    /// Decrypts a single base64 encoded value into raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SfdlKey::decrypt`], except that the plaintext is not checked
+    /// for UTF-8.
+    pub fn decrypt_bytes(&self, ciphertext: impl AsRef<[u8]>) -> Result<Vec<u8>, DecryptError> {
+        let decoded = decode(ciphertext.as_ref())?;
+        let (iv, ciphertext) = decoded
+            .split_first_chunk::<AES_BLOCK_SIZE>()
+            .filter(|(_, blocks)| !blocks.is_empty() && blocks.len() % AES_BLOCK_SIZE == 0)
+            .ok_or_else(|| length_error(decoded.len()))?;
+
+        self.decryptor(iv)
+            .decrypt_padded_vec::<Pkcs7>(ciphertext)
+            .map_err(|_| DecryptError::InvalidPassword)
+    }
+
+    /// Checks whether this key decrypts a single value, without decrypting
+    /// all of it.
+    ///
+    /// Only the last ciphertext block is decrypted, using the block before it
+    /// (or the IV) as the chaining value. Its PKCS#7 padding and the UTF-8 of
+    /// the plaintext in it are validated. An encrypted empty string consists
+    /// of a single padding block and is checked the same way.
+    ///
+    /// A wrong key passes this check only with negligible probability; a
+    /// subsequent full decryption still reports it.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SfdlKey::decrypt`].
+    pub fn verify(&self, ciphertext: impl AsRef<[u8]>) -> Result<(), DecryptError> {
+        let decoded = decode(ciphertext.as_ref())?;
+        if decoded.len() % AES_BLOCK_SIZE != 0 {
+            return Err(length_error(decoded.len()));
+        }
+
+        // `decode` guarantees the IV, so only a missing ciphertext block can fail.
+        let (head, last) = decoded
+            .split_last_chunk::<AES_BLOCK_SIZE>()
+            .ok_or_else(|| length_error(decoded.len()))?;
+        let chain = head
+            .last_chunk::<AES_BLOCK_SIZE>()
+            .ok_or_else(|| length_error(decoded.len()))?;
+
+        let mut decrypted = self
+            .decryptor(chain)
+            .decrypt_padded_vec::<Pkcs7>(last)
+            .map_err(|_| DecryptError::InvalidPassword)?;
+
+        // The block may start in the middle of a character from the previous block.
+        let split = if head.len() > AES_BLOCK_SIZE {
+            decrypted
+                .iter()
+                .take(3)
+                .take_while(|&&byte| byte & 0xC0 == 0x80)
+                .count()
+        } else {
+            0
+        };
+        let tail = decrypted[split..].to_vec();
+        wipe(&mut decrypted);
+        let mut text = String::from_utf8(tail)?;
+        wipe(&mut text);
+        Ok(())
+    }
+}
+
+/// Decodes base64 `ciphertext` that must at least hold an IV.
+fn decode(ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    let decoded = BASE64_STANDARD.decode(ciphertext.trim_ascii())?;
     if decoded.len() < AES_BLOCK_SIZE {
         return Err(DecryptError::InvalidCiphertextLength {
             expected: AES_BLOCK_SIZE,
             got: decoded.len(),
         });
     }
+    Ok(decoded)
+}
 
-    let (iv, ciphertext) = decoded.split_at(AES_BLOCK_SIZE);
-    let iv: &[u8; AES_BLOCK_SIZE] =
-        iv.try_into()
-            .map_err(|_| DecryptError::InvalidCiphertextLength {
-                expected: AES_BLOCK_SIZE,
-                got: iv.len(),
-            })?;
-
-    let decryptor = Decryptor::<Aes128>::new(key.into(), iv.into());
-    let decrypted = decryptor
-        .decrypt_padded_vec::<Pkcs7>(ciphertext)
-        .map_err(|_| DecryptError::InvalidPassword)?;
-
-    Ok(String::from_utf8(decrypted)?)
+/// The error for `got` decoded bytes that hold an IV but not a whole number
+/// of ciphertext blocks after it.
+fn length_error(got: usize) -> DecryptError {
+    DecryptError::InvalidCiphertextLength {
+        expected: got.next_multiple_of(AES_BLOCK_SIZE).max(2 * AES_BLOCK_SIZE),
+        got,
+    }
 }
 
-/// Encrypts a single value using the SFDL AES-128-CBC primitive.
+/// Encrypts a single value with `password` and a secure random IV.
 ///
 /// Empty passwords are accepted to match the reference implementation, even
-/// though they result in weak encryption.
-pub fn encrypt_value(data: &str, password: &str) -> String {
-    let digest = md5::compute(password.as_bytes());
-    let key: &[u8; AES_BLOCK_SIZE] = &digest.0;
+/// though they result in weak encryption. Use [`SfdlKey`] to encrypt several
+/// values without deriving the key each time.
+#[must_use]
+pub fn encrypt_value(plaintext: impl AsRef<[u8]>, password: &str) -> String {
+    SfdlKey::from_password(password).encrypt(plaintext)
+}
+
+/// Decrypts a single value with `password` into a string.
+///
+/// # Errors
+///
+/// See [`SfdlKey::decrypt`].
+pub fn decrypt_value(ciphertext: impl AsRef<[u8]>, password: &str) -> Result<String, DecryptError> {
+    SfdlKey::from_password(password).decrypt(ciphertext)
+}
 
-    let iv = rand::rng().random::<[u8; AES_BLOCK_SIZE]>();
-    let encryptor = Encryptor::<Aes128>::new(key.into(), (&iv).into());
-    let encrypted_data = encryptor.encrypt_padded_vec::<Pkcs7>(data.as_bytes());
-    let encrypted_data = [iv.to_vec(), encrypted_data].concat();
-    BASE64_STANDARD.encode(&encrypted_data)
+/// Decrypts a single value with `password` into raw bytes.
+///
+/// # Errors
+///
+/// See [`SfdlKey::decrypt_bytes`].
+pub fn decrypt_value_bytes(
+    ciphertext: impl AsRef<[u8]>,
+    password: &str,
+) -> Result<Vec<u8>, DecryptError> {
+    SfdlKey::from_password(password).decrypt_bytes(ciphertext)
 }
 
 /// Returns a new [`SfdlFile`] with all decryptable fields decrypted.
 ///
 /// The input is not modified. On error the original value remains unchanged.
-pub fn decrypt_sfdl(sfdl: &SfdlFile, password: &str) -> Result<SfdlFile, DecryptError> {
+pub(crate) fn decrypt_sfdl(sfdl: &SfdlFile, key: &SfdlKey) -> Result<SfdlFile, DecryptError> {
     let mut out = sfdl.clone();
-    decrypt_into(&mut out, password)?;
+    if let Err(err) = decrypt_into(&mut out, key) {
+        wipe_fields(&mut out);
+        return Err(err);
+    }
     Ok(out)
 }
 
-fn decrypt_into(sfdl: &mut SfdlFile, password: &str) -> Result<(), DecryptError> {
-    sfdl.description = decrypt_value(&sfdl.description, password)?;
-    sfdl.uploader = decrypt_value(&sfdl.uploader, password)?;
-    sfdl.connection_info.host = decrypt_value(&sfdl.connection_info.host, password)?;
-    sfdl.connection_info.password = decrypt_value(&sfdl.connection_info.password, password)?;
-    sfdl.connection_info.username = decrypt_value(&sfdl.connection_info.username, password)?;
-    sfdl.connection_info.default_path =
-        decrypt_value(&sfdl.connection_info.default_path, password)?;
+fn decrypt_into(sfdl: &mut SfdlFile, key: &SfdlKey) -> Result<(), DecryptError> {
+    visit_fields_mut(sfdl, |path, field| decrypt_field(key, path, field))
+}
+
+/// Decrypts a single field in place. A plaintext `DefaultPath` is kept.
+pub(crate) fn decrypt_field(
+    key: &SfdlKey,
+    path: FieldPath,
+    field: &mut String,
+) -> Result<(), DecryptError> {
+    if let Some(value) = decrypt_at(key, path, field)? {
+        replace(field, value);
+    }
+    Ok(())
+}
+
+/// Wipes every encryptable field of a partially decrypted copy.
+fn wipe_fields(sfdl: &mut SfdlFile) {
+    let Ok(()) = visit_fields_mut(sfdl, |_, field| {
+        wipe(field);
+        Ok::<_, Infallible>(())
+    });
+}
+
+/// Which fields a container encrypts.
+///
+/// The two reference implementations disagree about
+/// `ConnectionInfo.DefaultPath`: SFDL.Container leaves it plaintext, while
+/// SFDL.NET encrypts it. All other fields are encrypted by both. The profile
+/// only matters for encryption; when decrypting, `DefaultPath` is decrypted
+/// if it is ciphertext under the given key and kept as it is otherwise, so
+/// containers of either kind can be read.
+///
+/// # Example
+///
+/// ```rust
+/// # use sfdl::{EncryptionProfile, SfdlFile};
+/// let mut sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+/// let default_path = sfdl.connection_info.default_path.clone();
+///
+/// sfdl.encrypt_with_profile("S3cr3tP4ssw0rd!", EncryptionProfile::sfdl_container())
+///     .unwrap();
+/// assert_eq!(sfdl.connection_info.default_path, default_path);
+///
+/// sfdl.decrypt("S3cr3tP4ssw0rd!").unwrap();
+/// assert_eq!(sfdl.connection_info.default_path, default_path);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[must_use]
+pub struct EncryptionProfile {
+    /// Encrypt `ConnectionInfo.DefaultPath`.
+    pub default_path: bool,
+}
 
-    for pkg in sfdl.packages.iter_mut() {
-        pkg.package_name = decrypt_value(&pkg.package_name, password)?;
+impl Default for EncryptionProfile {
+    /// The SFDL.NET profile, which encrypts every field.
+    fn default() -> Self {
+        Self::sfdl_net()
+    }
+}
 
-        for folder in &mut pkg.bulk_folder_list.bulk_folder {
-            folder.bulk_folder_path = decrypt_value(&folder.bulk_folder_path, password)?;
-            folder.package_name = decrypt_value(&folder.package_name, password)?;
+impl EncryptionProfile {
+    /// The field set of `n0ix/SFDL.Container`: `DefaultPath` stays plaintext.
+    pub fn sfdl_container() -> Self {
+        Self {
+            default_path: false,
         }
+    }
+
+    /// The field set of SFDL.NET: every field is encrypted, `DefaultPath`
+    /// included. This is the default.
+    pub fn sfdl_net() -> Self {
+        Self { default_path: true }
+    }
 
-        if let Some(file_list) = pkg.file_list.as_mut() {
-            for file in &mut file_list.file_info {
-                file.directory_path = decrypt_value(&file.directory_path, password)?;
-                file.directory_root = decrypt_value(&file.directory_root, password)?;
-                file.file_name = decrypt_value(&file.file_name, password)?;
-                file.file_full_path = decrypt_value(&file.file_full_path, password)?;
-                file.package_name = decrypt_value(&file.package_name, password)?;
+    /// Set whether `ConnectionInfo.DefaultPath` is encrypted.
+    pub fn default_path(mut self, encrypt: bool) -> Self {
+        self.default_path = encrypt;
+        self
+    }
+}
+
+/// The path of `ConnectionInfo.DefaultPath`, the field whose encryption
+/// depends on the [`EncryptionProfile`].
+const DEFAULT_PATH: FieldPath = FieldPath::Root("connection_info.default_path");
+
+/// Decrypts the value of the field at `path`.
+///
+/// Returns `None` for a `DefaultPath` that is not ciphertext under `key`. A
+/// profile that leaves it plaintext can write any path, including one that
+/// happens to have the shape of ciphertext, so a `DefaultPath` that fails to
+/// decrypt is kept rather than failing the whole container.
+fn decrypt_at(key: &SfdlKey, path: FieldPath, field: &str) -> Result<Option<String>, DecryptError> {
+    if path != DEFAULT_PATH {
+        return key.decrypt(field).map(Some).map_err(|err| err.at(path));
+    }
+    if !looks_encrypted(field) {
+        return Ok(None);
+    }
+    Ok(key.decrypt(field).ok())
+}
+
+/// The location of an encryptable field, displayed like
+/// `packages[2].file_list.file_info[17].file_full_path`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FieldPath {
+    /// A field of `SFDLFile` or `ConnectionInfo`.
+    Root(&'static str),
+    /// A field of the package at the given index.
+    Package(usize, &'static str),
+    /// A field of a bulk folder, by package and folder index.
+    BulkFolder(usize, usize, &'static str),
+    /// A field of a file entry, by package and file index.
+    FileInfo(usize, usize, &'static str),
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Root(field) => f.write_str(field),
+            Self::Package(package, field) => write!(f, "packages[{package}].{field}"),
+            Self::BulkFolder(package, folder, field) => write!(
+                f,
+                "packages[{package}].bulk_folder_list.bulk_folder[{folder}].{field}"
+            ),
+            Self::FileInfo(package, file, field) => {
+                write!(f, "packages[{package}].file_list.file_info[{file}].{field}")
             }
         }
     }
+}
+
+/// Calls `visit` with the path and value of every encryptable field, in
+/// document order.
+fn visit_fields_mut<E>(
+    sfdl: &mut SfdlFile,
+    mut visit: impl FnMut(FieldPath, &mut String) -> Result<(), E>,
+) -> Result<(), E> {
+    visit(FieldPath::Root("description"), &mut sfdl.description)?;
+    visit(FieldPath::Root("uploader"), &mut sfdl.uploader)?;
+    visit_connection_fields(&mut sfdl.connection_info, &mut visit)?;
+    for (i, pkg) in sfdl.packages.iter_mut().enumerate() {
+        visit_package_fields(i, pkg, &mut visit)?;
+    }
 
     Ok(())
 }
 
-/// Returns a new [`SfdlFile`] with all encryptable fields encrypted.
+/// Calls `visit` for every encryptable field of `ConnectionInfo`.
+pub(crate) fn visit_connection_fields<E>(
+    connection: &mut ConnectionInfo,
+    visit: &mut impl FnMut(FieldPath, &mut String) -> Result<(), E>,
+) -> Result<(), E> {
+    use FieldPath::Root;
+
+    visit(Root("connection_info.host"), &mut connection.host)?;
+    visit(
+        Root("connection_info.password"),
+        connection.password.as_mut_string(),
+    )?;
+    visit(
+        Root("connection_info.username"),
+        connection.username.as_mut_string(),
+    )?;
+    visit(DEFAULT_PATH, &mut connection.default_path)
+}
+
+/// Calls `visit` for every encryptable field of the package at index `i`,
+/// including its bulk folders and file entries.
+pub(crate) fn visit_package_fields<E>(
+    i: usize,
+    pkg: &mut SfdlPackage,
+    visit: &mut impl FnMut(FieldPath, &mut String) -> Result<(), E>,
+) -> Result<(), E> {
+    use FieldPath::{BulkFolder, Package};
+
+    visit(Package(i, "package_name"), &mut pkg.package_name)?;
+
+    for (j, folder) in pkg.bulk_folder_list.bulk_folder.iter_mut().enumerate() {
+        visit(
+            BulkFolder(i, j, "bulk_folder_path"),
+            &mut folder.bulk_folder_path,
+        )?;
+        visit(BulkFolder(i, j, "package_name"), &mut folder.package_name)?;
+    }
+
+    if let Some(file_list) = pkg.file_list.as_mut() {
+        for (j, file) in file_list.file_info.iter_mut().enumerate() {
+            visit_file_fields(i, j, file, visit)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Calls `visit` for every encryptable field of file entry `j` of package `i`.
+pub(crate) fn visit_file_fields<E>(
+    i: usize,
+    j: usize,
+    file: &mut FileInfo,
+    visit: &mut impl FnMut(FieldPath, &mut String) -> Result<(), E>,
+) -> Result<(), E> {
+    use FieldPath::FileInfo;
+
+    visit(FileInfo(i, j, "directory_path"), &mut file.directory_path)?;
+    visit(FileInfo(i, j, "directory_root"), &mut file.directory_root)?;
+    visit(FileInfo(i, j, "file_name"), &mut file.file_name)?;
+    visit(FileInfo(i, j, "file_full_path"), &mut file.file_full_path)?;
+    visit(FileInfo(i, j, "package_name"), &mut file.package_name)
+}
+
+/// Returns every field that is encrypted under all profiles, in document
+/// order. `DefaultPath` is left out, see [`EncryptionProfile`].
+fn fields(sfdl: &SfdlFile) -> Vec<&str> {
+    let mut fields: Vec<&str> = vec![
+        &sfdl.description,
+        &sfdl.uploader,
+        &sfdl.connection_info.host,
+        &sfdl.connection_info.password,
+        &sfdl.connection_info.username,
+    ];
+
+    for pkg in sfdl.packages.iter() {
+        fields.push(&pkg.package_name);
+
+        for folder in &pkg.bulk_folder_list.bulk_folder {
+            fields.push(&folder.bulk_folder_path);
+            fields.push(&folder.package_name);
+        }
+
+        if let Some(file_list) = pkg.file_list.as_ref() {
+            for file in &file_list.file_info {
+                fields.push(&file.directory_path);
+                fields.push(&file.directory_root);
+                fields.push(&file.file_name);
+                fields.push(&file.file_full_path);
+                fields.push(&file.package_name);
+            }
+        }
+    }
+
+    fields
+}
+
+/// The encryption state of a container, judged by the content of its
+/// encryptable fields rather than its `Encrypted` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionState {
+    /// No non-empty field looks like ciphertext.
+    Plaintext,
+    /// Every non-empty field looks like ciphertext.
+    Encrypted,
+    /// Some fields look like ciphertext and others do not.
+    Mixed,
+}
+
+/// Returns whether `value` has the shape of an encrypted SFDL value: standard
+/// base64 of an IV followed by at least one AES block. Surrounding whitespace
+/// is ignored, as it is when decrypting.
+pub fn looks_encrypted(value: &str) -> bool {
+    BASE64_STANDARD
+        .decode(value.trim_ascii())
+        .is_ok_and(|decoded| {
+            decoded.len() >= 2 * AES_BLOCK_SIZE && decoded.len() % AES_BLOCK_SIZE == 0
+        })
+}
+
+/// Classifies `sfdl` by the non-empty encryptable fields that look encrypted.
+pub(crate) fn detect_state(sfdl: &SfdlFile) -> EncryptionState {
+    let (encrypted, plaintext): (Vec<_>, Vec<_>) = fields(sfdl)
+        .into_iter()
+        .filter(|field| !field.is_empty())
+        .partition(|field| looks_encrypted(field));
+
+    match (encrypted.is_empty(), plaintext.is_empty()) {
+        (false, false) => EncryptionState::Mixed,
+        (false, true) => EncryptionState::Encrypted,
+        (true, _) => EncryptionState::Plaintext,
+    }
+}
+
+/// Returns a new [`SfdlFile`] with every field that looks encrypted decrypted.
+///
+/// Fields that do not look encrypted, including empty ones, are kept as they
+/// are. The input is not modified.
+pub(crate) fn decrypt_detected(sfdl: &SfdlFile, key: &SfdlKey) -> Result<SfdlFile, DecryptError> {
+    let mut out = sfdl.clone();
+    let result = visit_fields_mut(&mut out, |path, field| {
+        if looks_encrypted(field) {
+            decrypt_field(key, path, field)?;
+        }
+        Ok::<_, DecryptError>(())
+    });
+    if let Err(err) = result {
+        wipe_fields(&mut out);
+        return Err(err);
+    }
+    Ok(out)
+}
+
+/// Which fields [`SfdlFile::decrypt_lenient`] decrypted and which it kept
+/// unchanged.
+///
+/// Fields are named by their path, like `connection_info.host` or
+/// `packages[0].package_name`, in document order. Empty fields are not listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecryptReport {
+    /// Fields that held valid ciphertext and were decrypted.
+    pub decrypted: Vec<String>,
+    /// Fields that were kept as they are, because they are not valid
+    /// ciphertext under the given key.
+    pub passed_through: Vec<String>,
+}
+
+impl DecryptReport {
+    /// Returns whether every non-empty field was decrypted.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.passed_through.is_empty()
+    }
+}
+
+/// Returns a new [`SfdlFile`] with every field decrypted that `key` decrypts,
+/// together with a report of the decrypted and passed through fields.
+///
+/// Fails with [`DecryptError::InvalidPassword`] if some field looks encrypted
+/// but none could be decrypted. The input is not modified.
+pub(crate) fn decrypt_lenient(
+    sfdl: &SfdlFile,
+    key: &SfdlKey,
+) -> Result<(SfdlFile, DecryptReport), DecryptError> {
+    let mut out = sfdl.clone();
+    let mut report = DecryptReport::default();
+    let mut looked_encrypted = false;
+    let Ok(()) = visit_fields_mut(&mut out, |path, field| {
+        if field.is_empty() {
+            return Ok(());
+        }
+        looked_encrypted |= looks_encrypted(field);
+        match key.decrypt(&**field) {
+            Ok(value) => {
+                replace(field, value);
+                report.decrypted.push(path.to_string());
+            }
+            Err(_) => report.passed_through.push(path.to_string()),
+        }
+        Ok::<_, Infallible>(())
+    });
+
+    if looked_encrypted && report.decrypted.is_empty() {
+        return Err(DecryptError::InvalidPassword);
+    }
+    Ok((out, report))
+}
+
+/// Returns a new [`SfdlFile`] with every field that does not look encrypted
+/// encrypted.
 ///
 /// The input is not modified.
-pub fn encrypt_sfdl(sfdl: &SfdlFile, password: &str) -> SfdlFile {
+pub(crate) fn encrypt_detected(sfdl: &SfdlFile, key: &SfdlKey, mut ivs: impl IvSource) -> SfdlFile {
     let mut out = sfdl.clone();
-    encrypt_into(&mut out, password);
+    let Ok(()) = visit_fields_mut(&mut out, |_, field| {
+        if !looks_encrypted(field) {
+            let value = key.encrypt_with(&**field, &mut ivs);
+            replace(field, value);
+        }
+        Ok::<_, Infallible>(())
+    });
     out
 }
 
-fn encrypt_into(sfdl: &mut SfdlFile, password: &str) {
-    sfdl.description = encrypt_value(&sfdl.description, password);
-    sfdl.uploader = encrypt_value(&sfdl.uploader, password);
-    sfdl.connection_info.host = encrypt_value(&sfdl.connection_info.host, password);
-    sfdl.connection_info.password = encrypt_value(&sfdl.connection_info.password, password);
-    sfdl.connection_info.username = encrypt_value(&sfdl.connection_info.username, password);
-    sfdl.connection_info.default_path = encrypt_value(&sfdl.connection_info.default_path, password);
+/// The candidate password that decrypted a container, as returned by
+/// [`SfdlFile::decrypt_with_candidates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateMatch {
+    /// Position of the password among the candidates, starting at `0`.
+    pub index: usize,
+    /// The matching password.
+    pub password: SecretString,
+}
+
+/// Returns whether `err` can be caused by a wrong password, as opposed to a
+/// malformed ciphertext that no password can decrypt.
+fn is_wrong_password(err: &DecryptError) -> bool {
+    matches!(
+        err,
+        DecryptError::InvalidPassword | DecryptError::InvalidEncoding(_)
+    )
+}
+
+/// Checks `key` against the shortest non-empty top-level field.
+///
+/// Only the last block of that field is decrypted, see [`SfdlKey::verify`].
+/// Fails with [`DecryptError::InvalidCiphertextLength`] if all of these fields
+/// are empty, as such a file cannot be decrypted either.
+pub(crate) fn verify_sfdl(sfdl: &SfdlFile, key: &SfdlKey) -> Result<(), DecryptError> {
+    let fields: [&str; 5] = [
+        &sfdl.description,
+        &sfdl.uploader,
+        &sfdl.connection_info.host,
+        &sfdl.connection_info.password,
+        &sfdl.connection_info.username,
+    ];
+    match fields
+        .into_iter()
+        .filter(|field| !field.is_empty())
+        .min_by_key(|field| field.len())
+    {
+        Some(field) => key.verify(field),
+        None => Err(DecryptError::InvalidCiphertextLength {
+            expected: AES_BLOCK_SIZE,
+            got: 0,
+        }),
+    }
+}
 
-    for pkg in sfdl.packages.iter_mut() {
-        pkg.package_name = encrypt_value(&pkg.package_name, password);
+/// Decrypts `sfdl` with `password`, or returns `None` if the password is wrong.
+fn try_candidate(sfdl: &SfdlFile, password: &str) -> Result<Option<SfdlFile>, DecryptError> {
+    let key = SfdlKey::from_password(password);
+    match verify_sfdl(sfdl, &key).and_then(|()| decrypt_sfdl(sfdl, &key)) {
+        Ok(decrypted) => Ok(Some(decrypted)),
+        Err(err) if is_wrong_password(err.cause()) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
 
-        for folder in &mut pkg.bulk_folder_list.bulk_folder {
-            folder.bulk_folder_path = encrypt_value(&folder.bulk_folder_path, password);
-            folder.package_name = encrypt_value(&folder.package_name, password);
+/// Tries `candidates` in order and returns the first one that decrypts `sfdl`
+/// together with the decrypted file.
+pub(crate) fn find_candidate<I>(
+    sfdl: &SfdlFile,
+    candidates: I,
+) -> Result<Option<(CandidateMatch, SfdlFile)>, DecryptError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    for (index, candidate) in candidates.into_iter().enumerate() {
+        let password = candidate.as_ref();
+        if let Some(decrypted) = try_candidate(sfdl, password)? {
+            let found = CandidateMatch {
+                index,
+                password: SecretString::new(password),
+            };
+            return Ok(Some((found, decrypted)));
         }
+    }
+    Ok(None)
+}
+
+/// Like [`find_candidate`], but tests the candidates on all available cores.
+///
+/// The result is the same as for the sequential search: the matching candidate
+/// with the lowest index.
+pub(crate) fn find_candidate_parallel<P>(
+    sfdl: &SfdlFile,
+    candidates: &[P],
+) -> Result<Option<(CandidateMatch, SfdlFile)>, DecryptError>
+where
+    P: AsRef<str> + Sync,
+{
+    let threads = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .clamp(1, candidates.len().max(1));
+    let best = AtomicUsize::new(usize::MAX);
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|offset| {
+                let best = &best;
+                scope.spawn(move || -> Result<_, DecryptError> {
+                    for index in (offset..candidates.len()).step_by(threads) {
+                        if index > best.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if let Some(decrypted) = try_candidate(sfdl, candidates[index].as_ref())? {
+                            best.fetch_min(index, Ordering::Relaxed);
+                            return Ok(Some((index, decrypted)));
+                        }
+                    }
+                    Ok(None)
+                })
+            })
+            .collect();
 
-        if let Some(file_list) = pkg.file_list.as_mut() {
-            for file in &mut file_list.file_info {
-                file.directory_path = encrypt_value(&file.directory_path, password);
-                file.directory_root = encrypt_value(&file.directory_root, password);
-                file.file_name = encrypt_value(&file.file_name, password);
-                file.file_full_path = encrypt_value(&file.file_full_path, password);
-                file.package_name = encrypt_value(&file.package_name, password);
+        let mut found: Option<(usize, SfdlFile)> = None;
+        for worker in workers {
+            let result = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+            if let Some((index, decrypted)) = result {
+                if found.as_ref().is_none_or(|(best, _)| index < *best) {
+                    found = Some((index, decrypted));
+                }
             }
         }
-    }
+
+        Ok(found.map(|(index, decrypted)| {
+            let found = CandidateMatch {
+                index,
+                password: SecretString::new(candidates[index].as_ref()),
+            };
+            (found, decrypted)
+        }))
+    })
+}
+
+/// Returns a new [`SfdlFile`] with the fields of `profile` encrypted.
+///
+/// The input is not modified.
+pub(crate) fn encrypt_sfdl(
+    sfdl: &SfdlFile,
+    key: &SfdlKey,
+    profile: EncryptionProfile,
+    mut ivs: impl IvSource,
+) -> SfdlFile {
+    let mut out = sfdl.clone();
+    encrypt_into(&mut out, key, profile, &mut ivs);
+    out
+}
+
+fn encrypt_into(
+    sfdl: &mut SfdlFile,
+    key: &SfdlKey,
+    profile: EncryptionProfile,
+    ivs: &mut impl IvSource,
+) {
+    let Ok(()) = visit_fields_mut(sfdl, |path, field| {
+        if path == DEFAULT_PATH && !profile.default_path {
+            return Ok(());
+        }
+        let value = key.encrypt_with(&**field, ivs);
+        replace(field, value);
+        Ok::<_, Infallible>(())
+    });
+}
+
+/// Returns a new [`SfdlFile`] with every field decrypted with `old` and
+/// encrypted again with `new`.
+///
+/// Each plaintext only exists while its own field is processed. The input is
+/// not modified, so on error the original value remains unchanged.
+pub(crate) fn rekey_sfdl(
+    sfdl: &SfdlFile,
+    old: &SfdlKey,
+    new: &SfdlKey,
+    mut ivs: impl IvSource,
+) -> Result<SfdlFile, DecryptError> {
+    let mut out = sfdl.clone();
+    visit_fields_mut(&mut out, |path, field| {
+        let Some(mut plaintext) = decrypt_at(old, path, field)? else {
+            return Ok(());
+        };
+        let value = new.encrypt_with(&plaintext, &mut ivs);
+        wipe(&mut plaintext);
+        replace(field, value);
+        Ok::<_, DecryptError>(())
+    })?;
+    Ok(out)
 }
 
 #[cfg(test)]
@@ -189,6 +904,20 @@ mod tests {
     };
 
     use super::*;
+    use crate::iv::FixedIvSource;
+
+    fn decrypt_sfdl(sfdl: &SfdlFile, password: &str) -> Result<SfdlFile, DecryptError> {
+        super::decrypt_sfdl(sfdl, &SfdlKey::from_password(password))
+    }
+
+    fn encrypt_sfdl(sfdl: &SfdlFile, password: &str) -> SfdlFile {
+        super::encrypt_sfdl(
+            sfdl,
+            &SfdlKey::from_password(password),
+            EncryptionProfile::default(),
+            OsIvSource,
+        )
+    }
 
     #[test]
     fn test_decrypt_entry() {
@@ -281,8 +1010,8 @@ mod tests {
             encrypted: true,
             connection_info: ConnectionInfo {
                 host: "MyHost".to_string(),
-                password: "MyPassword".to_string(),
-                username: "MyUsername".to_string(),
+                password: "MyPassword".into(),
+                username: "MyUsername".into(),
                 default_path: "MyDefaultPath".to_string(),
                 ..Default::default()
             },
@@ -346,6 +1075,349 @@ mod tests {
         assert_eq!(decrypted, sfdl);
     }
 
+    #[test]
+    fn test_key_from_password() {
+        let password = "S3cr3tP4ssw0rd!";
+        let key = SfdlKey::from_password(password);
+        assert_eq!(key, SfdlKey::from_bytes(md5::compute(password).0));
+        assert_eq!(format!("{key:?}"), "SfdlKey { .. }");
+
+        let encrypted = key.encrypt("MyTestString1");
+        assert_eq!(
+            decrypt_value(&encrypted, password).unwrap(),
+            "MyTestString1"
+        );
+
+        let sfdl = sample_sfdl();
+        let encrypted = encrypt_sfdl(&sfdl, password);
+        assert_eq!(super::decrypt_sfdl(&encrypted, &key).unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_encrypt_value_with_fixed_iv() {
+        let key = SfdlKey::from_password("S3cr3tP4ssw0rd!");
+        let mut ivs = FixedIvSource::new([[0x21; AES_BLOCK_SIZE]]);
+
+        let encrypted = key.encrypt_with("MyTestString1", &mut ivs);
+        let decoded = BASE64_STANDARD.decode(&encrypted).unwrap();
+        assert_eq!(decoded[..AES_BLOCK_SIZE], [0x21; AES_BLOCK_SIZE]);
+        assert_eq!(encrypted, key.encrypt_with("MyTestString1", &mut ivs));
+        assert_eq!(key.decrypt(&encrypted).unwrap(), "MyTestString1");
+    }
+
+    #[test]
+    fn test_detect_state() {
+        let password = "S3cr3tP4ssw0rd!";
+        let sfdl = sample_sfdl();
+        assert_eq!(detect_state(&sfdl), EncryptionState::Plaintext);
+        assert_eq!(
+            detect_state(&SfdlFile::default()),
+            EncryptionState::Plaintext
+        );
+
+        let mut encrypted = encrypt_sfdl(&sfdl, password);
+        assert_eq!(detect_state(&encrypted), EncryptionState::Encrypted);
+
+        encrypted.uploader = String::new();
+        assert_eq!(detect_state(&encrypted), EncryptionState::Encrypted);
+
+        encrypted.connection_info.host = "ftp.example.com".to_string();
+        assert_eq!(detect_state(&encrypted), EncryptionState::Mixed);
+    }
+
+    #[test]
+    fn test_detected_round_trip() {
+        let password = "S3cr3tP4ssw0rd!";
+        let sfdl = sample_sfdl();
+
+        let mut mixed = encrypt_sfdl(&sfdl, password);
+        mixed.connection_info.host = sfdl.connection_info.host.clone();
+        mixed.packages[0].package_name = sfdl.packages[0].package_name.clone();
+
+        assert_eq!(
+            decrypt_detected(&mixed, &SfdlKey::from_password(password)).unwrap(),
+            sfdl
+        );
+
+        let encrypted = encrypt_detected(&mixed, &SfdlKey::from_password(password), OsIvSource);
+        assert_eq!(detect_state(&encrypted), EncryptionState::Encrypted);
+        assert_eq!(encrypted.uploader, mixed.uploader);
+        assert_eq!(decrypt_sfdl(&encrypted, password).unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_sfdl_container_profile() {
+        let mut sfdl = sample_sfdl();
+        sfdl.connection_info.default_path = "/".to_string();
+        let key = SfdlKey::from_password("S3cr3tP4ssw0rd!");
+        let profile = EncryptionProfile::sfdl_container();
+
+        let encrypted = super::encrypt_sfdl(&sfdl, &key, profile, OsIvSource);
+        assert_eq!(encrypted.connection_info.default_path, "/");
+        assert_eq!(detect_state(&encrypted), EncryptionState::Encrypted);
+        assert!(verify_sfdl(&encrypted, &key).is_ok());
+        assert_eq!(super::decrypt_sfdl(&encrypted, &key).unwrap(), sfdl);
+
+        let new = SfdlKey::from_password("new");
+        let rekeyed = rekey_sfdl(&encrypted, &key, &new, OsIvSource).unwrap();
+        assert_eq!(rekeyed.connection_info.default_path, "/");
+        assert_eq!(super::decrypt_sfdl(&rekeyed, &new).unwrap(), sfdl);
+
+        let encrypted = super::encrypt_sfdl(&sfdl, &key, EncryptionProfile::sfdl_net(), OsIvSource);
+        assert!(looks_encrypted(&encrypted.connection_info.default_path));
+        assert_eq!(super::decrypt_sfdl(&encrypted, &key).unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_plaintext_default_path_shaped_like_ciphertext() {
+        let mut sfdl = sample_sfdl();
+        // Only base64 characters, and 48 bytes once decoded.
+        sfdl.connection_info.default_path =
+            "/srv/ftp/archive/2024/season/one/episodes/complete/releases/misc".to_string();
+        assert!(looks_encrypted(&sfdl.connection_info.default_path));
+        let key = SfdlKey::from_password("S3cr3tP4ssw0rd!");
+
+        let encrypted =
+            super::encrypt_sfdl(&sfdl, &key, EncryptionProfile::sfdl_container(), OsIvSource);
+        assert_eq!(
+            encrypted.connection_info.default_path,
+            sfdl.connection_info.default_path
+        );
+        assert_eq!(super::decrypt_sfdl(&encrypted, &key).unwrap(), sfdl);
+        assert_eq!(decrypt_detected(&encrypted, &key).unwrap(), sfdl);
+
+        let new = SfdlKey::from_password("new");
+        let rekeyed = rekey_sfdl(&encrypted, &key, &new, OsIvSource).unwrap();
+        assert_eq!(
+            rekeyed.connection_info.default_path,
+            sfdl.connection_info.default_path
+        );
+        assert_eq!(super::decrypt_sfdl(&rekeyed, &new).unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_ciphertext_with_surrounding_whitespace() {
+        let sfdl = sample_sfdl();
+        let key = SfdlKey::from_password("S3cr3tP4ssw0rd!");
+        let mut encrypted =
+            super::encrypt_sfdl(&sfdl, &key, EncryptionProfile::default(), OsIvSource);
+        let default_path = format!("\n    {}\n", encrypted.connection_info.default_path);
+        encrypted.connection_info.default_path = default_path.clone();
+
+        assert!(looks_encrypted(&default_path));
+        assert_eq!(super::decrypt_sfdl(&encrypted, &key).unwrap(), sfdl);
+
+        // Already encrypted, so it is not encrypted a second time.
+        let detected = encrypt_detected(&encrypted, &key, OsIvSource);
+        assert_eq!(detected.connection_info.default_path, default_path);
+    }
+
+    #[test]
+    fn test_decrypt_lenient() {
+        let sfdl = sample_sfdl();
+        let key = SfdlKey::from_password("S3cr3tP4ssw0rd!");
+        let mut partial =
+            super::encrypt_sfdl(&sfdl, &key, EncryptionProfile::default(), OsIvSource);
+        partial.packages[0].package_name = sfdl.packages[0].package_name.clone();
+        // Looks like ciphertext, but is not valid under the key.
+        partial.uploader = BASE64_STANDARD.encode([0u8; 32]);
+        partial.packages[0].bulk_folder_list.bulk_folder[0].package_name = String::new();
+
+        let (decrypted, report) = decrypt_lenient(&partial, &key).unwrap();
+        assert_eq!(
+            report.passed_through,
+            ["uploader", "packages[0].package_name"]
+        );
+        assert_eq!(
+            report.decrypted[..2],
+            ["description", "connection_info.host"]
+        );
+        assert!(!report.is_complete());
+        assert_eq!(decrypted.description, sfdl.description);
+        assert_eq!(decrypted.uploader, partial.uploader);
+        assert_eq!(
+            decrypted.packages[0].package_name,
+            sfdl.packages[0].package_name
+        );
+
+        let wrong = SfdlKey::from_password("wrong");
+        assert!(matches!(
+            decrypt_lenient(&partial, &wrong),
+            Err(DecryptError::InvalidPassword)
+        ));
+
+        let (unchanged, report) = decrypt_lenient(&sfdl, &key).unwrap();
+        assert_eq!(unchanged, sfdl);
+        assert!(report.decrypted.is_empty());
+    }
+
+    #[test]
+    fn test_rekey_sfdl() {
+        let sfdl = sample_sfdl();
+        let old = SfdlKey::from_password("old");
+        let new = SfdlKey::from_password("new");
+        let encrypted = super::encrypt_sfdl(&sfdl, &old, EncryptionProfile::default(), OsIvSource);
+
+        let rekeyed = rekey_sfdl(&encrypted, &old, &new, OsIvSource).unwrap();
+        assert_eq!(super::decrypt_sfdl(&rekeyed, &new).unwrap(), sfdl);
+        assert!(super::decrypt_sfdl(&rekeyed, &old).is_err());
+
+        let err = rekey_sfdl(&encrypted, &new, &old, OsIvSource).unwrap_err();
+        assert_eq!(err.path(), Some("description"));
+    }
+
+    #[test]
+    fn test_verify_value() {
+        let password = "S3cr3tP4ssw0rd!";
+        let key = SfdlKey::from_password(password);
+        let wrong = SfdlKey::from_password("wrong");
+        for value in [
+            "",
+            "MyTestString1",
+            "exactly16bytes!!",
+            "Grüße aus Köln, 東京",
+        ] {
+            let encrypted = encrypt_value(value, password);
+            assert_eq!(key.verify(&encrypted), Ok(()), "{value}");
+            assert!(wrong.verify(&encrypted).is_err(), "{value}");
+        }
+
+        assert!(matches!(
+            key.verify("AAAA"),
+            Err(DecryptError::InvalidCiphertextLength { got: 3, .. })
+        ));
+        for (len, expected) in [(16, 32), (33, 48)] {
+            let malformed = BASE64_STANDARD.encode(vec![0u8; len]);
+            let err = || DecryptError::InvalidCiphertextLength { expected, got: len };
+            assert_eq!(key.verify(&malformed), Err(err()), "{len}");
+            assert_eq!(key.decrypt(&malformed), Err(err()), "{len}");
+        }
+    }
+
+    #[test]
+    fn test_verify_sfdl() {
+        let password = "S3cr3tP4ssw0rd!";
+        let key = SfdlKey::from_password(password);
+        let mut encrypted = encrypt_sfdl(&sample_sfdl(), password);
+        assert_eq!(verify_sfdl(&encrypted, &key), Ok(()));
+        assert!(verify_sfdl(&encrypted, &SfdlKey::from_password("wrong")).is_err());
+
+        // Fields left empty by other generators are skipped.
+        encrypted.uploader = String::new();
+        assert_eq!(verify_sfdl(&encrypted, &key), Ok(()));
+
+        encrypted.description = String::new();
+        encrypted.connection_info.host = String::new();
+        encrypted.connection_info.password = SecretString::default();
+        encrypted.connection_info.username = SecretString::default();
+        encrypted.connection_info.default_path = String::new();
+        assert!(matches!(
+            verify_sfdl(&encrypted, &key),
+            Err(DecryptError::InvalidCiphertextLength { got: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_sfdl_malformed_field() {
+        let password = "S3cr3tP4ssw0rd!";
+        let mut encrypted = encrypt_sfdl(&sample_sfdl(), password);
+        encrypted.uploader = BASE64_STANDARD.encode([0u8; 17]);
+
+        let err = verify_sfdl(&encrypted, &SfdlKey::from_password("wrong")).unwrap_err();
+        assert!(!is_wrong_password(&err));
+        assert!(try_candidate(&encrypted, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_error_paths() {
+        let password = "S3cr3tP4ssw0rd!";
+        let mut encrypted = encrypt_sfdl(&sample_sfdl(), password);
+        encrypted.packages[0].bulk_folder_list.bulk_folder[0].package_name =
+            "not base64!".to_string();
+
+        let err = decrypt_sfdl(&encrypted, password).unwrap_err();
+        assert_eq!(
+            err.path(),
+            Some("packages[0].bulk_folder_list.bulk_folder[0].package_name")
+        );
+        assert!(matches!(err.cause(), DecryptError::InvalidData(_)));
+        assert_eq!(
+            err.to_string(),
+            "failed to decrypt `packages[0].bulk_folder_list.bulk_folder[0].package_name`"
+        );
+
+        let err = decrypt_sfdl(&encrypted, "wrong").unwrap_err();
+        assert_eq!(err.path(), Some("description"));
+        // A wrong key occasionally yields valid padding and fails on the UTF-8 instead.
+        assert!(is_wrong_password(err.cause()));
+    }
+
+    #[test]
+    fn test_field_path_display() {
+        assert_eq!(
+            FieldPath::Root("connection_info.host").to_string(),
+            "connection_info.host"
+        );
+        assert_eq!(
+            FieldPath::Package(1, "package_name").to_string(),
+            "packages[1].package_name"
+        );
+        assert_eq!(
+            FieldPath::FileInfo(2, 17, "file_full_path").to_string(),
+            "packages[2].file_list.file_info[17].file_full_path"
+        );
+    }
+
+    #[test]
+    fn test_find_candidate() {
+        let password = "S3cr3tP4ssw0rd!";
+        let sfdl = sample_sfdl();
+        let encrypted = encrypt_sfdl(&sfdl, password);
+
+        let candidates = ["wrong", "", password, "also-wrong"];
+        let (found, decrypted) = find_candidate(&encrypted, candidates).unwrap().unwrap();
+        assert_eq!(found.index, 2);
+        assert_eq!(found.password, password);
+        assert_eq!(decrypted, sfdl);
+
+        assert!(find_candidate(&encrypted, ["wrong"]).unwrap().is_none());
+        assert!(find_candidate(&encrypted, Vec::<String>::new())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_find_candidate_parallel_prefers_lowest_index() {
+        let password = "S3cr3tP4ssw0rd!";
+        let sfdl = sample_sfdl();
+        let encrypted = encrypt_sfdl(&sfdl, password);
+
+        let mut candidates: Vec<String> = (0..64).map(|i| format!("wrong-{i}")).collect();
+        candidates[40] = password.to_string();
+        candidates[50] = password.to_string();
+
+        let (found, decrypted) = find_candidate_parallel(&encrypted, &candidates)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.index, 40);
+        assert_eq!(decrypted, sfdl);
+
+        assert!(find_candidate_parallel(&encrypted, &candidates[..40])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_find_candidate_malformed_ciphertext() {
+        let mut encrypted = encrypt_sfdl(&sample_sfdl(), "S3cr3tP4ssw0rd!");
+        encrypted.uploader = "not base64!".to_string();
+
+        assert!(matches!(
+            find_candidate(&encrypted, ["S3cr3tP4ssw0rd!"]),
+            Err(DecryptError::InvalidData(_))
+        ));
+    }
+
     #[test]
     fn test_round_trip_multiple_packages_and_bulk_folders() {
         let password = "S3cr3tP4ssw0rd!";
@@ -355,8 +1427,8 @@ mod tests {
             encrypted: true,
             connection_info: ConnectionInfo {
                 host: "host1".to_string(),
-                username: "user1".to_string(),
-                password: "pass1".to_string(),
+                username: "user1".into(),
+                password: "pass1".into(),
                 default_path: "/default".to_string(),
                 ..Default::default()
             },
@@ -406,8 +1478,8 @@ mod tests {
             encrypted: true,
             connection_info: ConnectionInfo {
                 host: "host".to_string(),
-                username: "user".to_string(),
-                password: "pass".to_string(),
+                username: "user".into(),
+                password: "pass".into(),
                 default_path: "/".to_string(),
                 ..Default::default()
             },
@@ -440,6 +1512,7 @@ mod tests {
                             },
                         ],
                     }),
+                    ..Default::default()
                 }],
             },
             ..Default::default()
@@ -0,0 +1,240 @@
+//! Detection and conversion of the text encoding of SFDL containers.
+//!
+//! SFDL.NET writes UTF-8, but containers edited or re-saved by Windows tools
+//! are regularly UTF-16 or a legacy code page. [`SfdlFile::from_bytes`]
+//! detects the encoding from the byte order mark, the encoding named in the
+//! XML declaration or, as a last resort, falls back to Windows-1252 for input
+//! that is not valid UTF-8. The detected [`SourceEncoding`] can be passed to
+//! [`WriteOptions::source_encoding`] to write the file back the same way.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use sfdl::{SfdlFile, WriteOptions};
+//! let xml = std::fs::read_to_string("examples/decrypted.sfdl").unwrap();
+//! let mut bytes = vec![0xFF, 0xFE];
+//! bytes.extend(xml.encode_utf16().flat_map(u16::to_le_bytes));
+//!
+//! let (sfdl, source) = SfdlFile::from_bytes(&bytes).unwrap();
+//! assert_eq!(source.encoding, sfdl::encoding_rs::UTF_16LE);
+//! assert!(source.bom);
+//!
+//! let options = WriteOptions::new().source_encoding(source);
+//! assert_eq!(sfdl.to_xml_bytes_with(&options).unwrap()[..2], [0xFF, 0xFE]);
+//! ```
+//!
+//! [`SfdlFile::from_bytes`]: crate::SfdlFile::from_bytes
+//! [`WriteOptions::source_encoding`]: crate::WriteOptions::source_encoding
+
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use crate::error::ParseError;
+
+/// The text encoding a container was read in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceEncoding {
+    /// The detected encoding.
+    pub encoding: &'static Encoding,
+    /// Whether the input started with a byte order mark.
+    pub bom: bool,
+}
+
+impl Default for SourceEncoding {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+        }
+    }
+}
+
+/// Decodes `bytes` into text and reports the encoding that was used.
+pub(crate) fn decode(bytes: &[u8]) -> Result<(Cow<'_, str>, SourceEncoding), ParseError> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return decode_with(&bytes[bom_length..], encoding, true);
+    }
+    if bytes.starts_with(b"<\0") {
+        return decode_with(bytes, UTF_16LE, false);
+    }
+    if bytes.starts_with(b"\0<") {
+        return decode_with(bytes, UTF_16BE, false);
+    }
+
+    let encoding = match declared_encoding(bytes) {
+        Some(label) => match Encoding::for_label(label) {
+            // The bytes are ASCII compatible, so a UTF-16 declaration is wrong.
+            Some(encoding) if encoding == UTF_16LE || encoding == UTF_16BE => UTF_8,
+            Some(encoding) => encoding,
+            None => {
+                return Err(ParseError::UnsupportedEncoding(
+                    String::from_utf8_lossy(label).into_owned(),
+                ))
+            }
+        },
+        None if std::str::from_utf8(bytes).is_ok() => UTF_8,
+        None => WINDOWS_1252,
+    };
+    decode_with(bytes, encoding, false)
+}
+
+fn decode_with<'a>(
+    bytes: &'a [u8],
+    encoding: &'static Encoding,
+    bom: bool,
+) -> Result<(Cow<'a, str>, SourceEncoding), ParseError> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| (text, SourceEncoding { encoding, bom }))
+        .ok_or(ParseError::MalformedInput {
+            encoding: encoding.name(),
+        })
+}
+
+/// Returns the `encoding` pseudo-attribute of a leading XML declaration.
+fn declared_encoding(bytes: &[u8]) -> Option<&[u8]> {
+    let declaration = bytes.strip_prefix(b"<?xml")?;
+    let end = declaration.windows(2).position(|window| window == b"?>")?;
+    let declaration = &declaration[..end];
+
+    let start = declaration
+        .windows(8)
+        .position(|window| window == b"encoding")?;
+    let value = declaration[start + 8..]
+        .trim_ascii_start()
+        .strip_prefix(b"=")?
+        .trim_ascii_start();
+    let (&quote, value) = value.split_first()?;
+    if quote != b'"' && quote != b'\'' {
+        return None;
+    }
+    let length = value.iter().position(|&byte| byte == quote)?;
+    Some(&value[..length])
+}
+
+/// The label written into the XML declaration for `encoding`.
+pub(crate) fn declaration_label(encoding: &'static Encoding) -> Cow<'static, str> {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        Cow::Borrowed("utf-16")
+    } else {
+        Cow::Owned(encoding.name().to_ascii_lowercase())
+    }
+}
+
+/// Encodes `text` as `encoding`, prefixed with a byte order mark if `bom` is
+/// set and the encoding has one.
+///
+/// Characters the encoding cannot represent are written as numeric character
+/// references.
+pub(crate) fn encode(text: &str, encoding: &'static Encoding, bom: bool) -> Vec<u8> {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+        let units = bom.then_some(0xFEFF).into_iter().chain(text.encode_utf16());
+        for unit in units {
+            if encoding == UTF_16LE {
+                bytes.extend(unit.to_le_bytes());
+            } else {
+                bytes.extend(unit.to_be_bytes());
+            }
+        }
+        return bytes;
+    }
+
+    let (encoded, _, _) = encoding.encode(text);
+    if bom && encoding == UTF_8 {
+        let mut bytes = Vec::with_capacity(encoded.len() + 3);
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+        bytes.extend_from_slice(&encoded);
+        bytes
+    } else {
+        encoded.into_owned()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::{SfdlFile, WriteOptions};
+
+    fn sample() -> String {
+        std::fs::read_to_string("tests/data/mixed_mode.xml")
+            .unwrap()
+            .replace("Mixed mode description", "Grüße")
+    }
+
+    #[test]
+    fn test_utf8_without_bom() {
+        let xml = sample();
+        let (sfdl, source) = SfdlFile::from_bytes(xml.as_bytes()).unwrap();
+        assert_eq!(source, SourceEncoding::default());
+        assert_eq!(sfdl.description, "Grüße");
+    }
+
+    #[test]
+    fn test_utf16_round_trip() {
+        for (encoding, bom) in [(UTF_16LE, true), (UTF_16BE, true), (UTF_16LE, false)] {
+            let expected: SfdlFile = sample().parse().unwrap();
+            let options = WriteOptions::new()
+                .declaration(true)
+                .source_encoding(SourceEncoding { encoding, bom });
+            let bytes = expected.to_xml_bytes_with(&options).unwrap();
+
+            let (sfdl, source) = SfdlFile::from_bytes(&bytes).unwrap();
+            assert_eq!(source, SourceEncoding { encoding, bom });
+            assert_eq!(sfdl, expected);
+        }
+    }
+
+    #[test]
+    fn test_declared_code_page() {
+        let xml = sample()
+            .replace("encoding=\"utf-8\"", "encoding='ISO-8859-15'")
+            .replace("Grüße", "5 €");
+        let (bytes, _, _) = encoding_rs::ISO_8859_15.encode(&xml);
+
+        let (sfdl, source) = SfdlFile::from_bytes(&bytes).unwrap();
+        assert_eq!(source.encoding, encoding_rs::ISO_8859_15);
+        assert_eq!(sfdl.description, "5 €");
+    }
+
+    #[test]
+    fn test_invalid_utf8_falls_back_to_windows_1252() {
+        let xml = sample().replace(r#"<?xml version="1.0" encoding="utf-8"?>"#, "");
+        let (bytes, _, _) = WINDOWS_1252.encode(&xml);
+
+        let (sfdl, source) = SfdlFile::from_bytes(&bytes).unwrap();
+        assert_eq!(source.encoding, WINDOWS_1252);
+        assert_eq!(sfdl.description, "Grüße");
+    }
+
+    #[test]
+    fn test_unknown_declared_encoding() {
+        let xml = sample().replace("utf-8", "x-nope");
+        assert!(matches!(
+            SfdlFile::from_bytes(xml.as_bytes()),
+            Err(ParseError::UnsupportedEncoding(label)) if label == "x-nope"
+        ));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let mut bytes = b"\xEF\xBB\xBF".to_vec();
+        bytes.extend_from_slice(b"<SFDLFile>\xFF</SFDLFile>");
+        assert!(matches!(
+            SfdlFile::from_bytes(&bytes),
+            Err(ParseError::MalformedInput { encoding: "UTF-8" })
+        ));
+    }
+
+    #[test]
+    fn test_declaration_names_encoding() {
+        let sfdl: SfdlFile = sample().parse().unwrap();
+        let options = WriteOptions::new().declaration(true).encoding(WINDOWS_1252);
+
+        let bytes = sfdl.to_xml_bytes_with(&options).unwrap();
+        assert!(bytes.starts_with(b"<?xml version=\"1.0\" encoding=\"windows-1252\"?>"));
+        assert!(bytes.windows(4).any(|window| window == b"Gr\xFC\xDF"));
+    }
+}
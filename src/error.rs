@@ -1,21 +1,8 @@
-//! Error types for io, parsing, encrypting and decrypting SFDL files.
+//! Error types for io, parsing, converting, building, migrating and decrypting
+//! SFDL files.
 
 use thiserror::Error;
 
-/// Error returned when encrypting an SFDL value fails.
-#[derive(Error, Debug, PartialEq, Eq)]
-pub enum EncryptError {
-    /// Base64 decoding of the input failed.
-    #[error("invalid data")]
-    InvalidData(#[from] base64::DecodeError),
-    /// The decrypted bytes are not valid UTF-8.
-    #[error("invalid encoding, expected utf-8")]
-    InvalidEncoding(#[from] std::string::FromUtf8Error),
-    /// An unexpected encryption error occurred.
-    #[error("unknown encryption error")]
-    Unknown,
-}
-
 /// Error returned when decrypting an SFDL value fails.
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum DecryptError {
@@ -25,7 +12,8 @@ pub enum DecryptError {
     /// The decrypted bytes are not valid UTF-8.
     #[error("invalid encoding, expected utf-8")]
     InvalidEncoding(#[from] std::string::FromUtf8Error),
-    /// The ciphertext is too short to contain an IV.
+    /// The ciphertext is too short to contain an IV and a block, or is not a
+    /// whole number of blocks.
     #[error("invalid ciphertext length: expected at least {expected} bytes, got {got}")]
     InvalidCiphertextLength {
         /// Expected minimum length in bytes.
@@ -36,9 +24,44 @@ pub enum DecryptError {
     /// The password is wrong or the ciphertext has been tampered with.
     #[error("invalid password")]
     InvalidPassword,
-    /// An unexpected decryption error occurred.
-    #[error("unknown decryption error")]
-    Unknown,
+    /// Decrypting a field of a container failed.
+    #[error("failed to decrypt `{path}`")]
+    Field {
+        /// Path of the field, e.g. `packages[2].file_list.file_info[17].file_full_path`.
+        path: String,
+        /// The error for the field's value.
+        source: Box<DecryptError>,
+    },
+}
+
+impl DecryptError {
+    /// Path of the field that failed to decrypt, if known.
+    #[must_use]
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Field { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The underlying error, without the field it occurred in.
+    ///
+    /// Use this to match on the kind of failure, e.g.
+    /// [`DecryptError::InvalidPassword`], regardless of the field.
+    #[must_use]
+    pub fn cause(&self) -> &Self {
+        match self {
+            Self::Field { source, .. } => source.cause(),
+            other => other,
+        }
+    }
+
+    pub(crate) fn at(self, path: impl std::fmt::Display) -> Self {
+        Self::Field {
+            path: path.to_string(),
+            source: Box::new(self),
+        }
+    }
 }
 
 /// Error returned when parsing or serializing SFDL XML fails.
@@ -50,20 +73,96 @@ pub enum ParseError {
     /// Serializing the [`SfdlFile`](crate::SfdlFile) into XML failed.
     #[error("invalid xml serialize")]
     InvalidSfdlSerialize(#[from] quick_xml::SeError),
+    /// The XML declaration names an encoding that is not supported.
+    #[error("unsupported encoding `{0}`")]
+    UnsupportedEncoding(String),
+    /// The input is not valid in its detected encoding.
+    #[error("input is not valid {encoding}")]
+    MalformedInput {
+        /// Name of the detected encoding.
+        encoding: &'static str,
+    },
+}
+
+/// Error returned when converting a container to or from JSON, YAML or TOML
+/// fails.
+///
+/// Each variant exists only if the feature of its format is enabled.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum FormatError {
+    /// JSON serialization or deserialization error.
+    #[cfg(feature = "json")]
+    #[error("invalid json")]
+    Json(#[from] serde_json::Error),
+    /// YAML serialization or deserialization error.
+    #[cfg(feature = "yaml")]
+    #[error("invalid yaml")]
+    Yaml(#[from] serde_yaml_ng::Error),
+    /// TOML deserialization error.
+    #[cfg(feature = "toml")]
+    #[error("invalid toml")]
+    TomlDeserialize(#[from] toml::de::Error),
+    /// TOML serialization error.
+    #[cfg(feature = "toml")]
+    #[error("invalid toml serialize")]
+    TomlSerialize(#[from] toml::ser::Error),
+}
+
+/// Error returned when a builder is missing required data.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// No host, or an empty host, was given for the connection.
+    #[error("missing host")]
+    MissingHost,
+    /// The connection port is `0`.
+    #[error("invalid port 0")]
+    InvalidPort,
+    /// No connection settings were given for the container.
+    #[error("missing connection info")]
+    MissingConnectionInfo,
+    /// The container has no packages.
+    #[error("no packages")]
+    NoPackages,
+    /// A package has neither bulk folders nor file entries.
+    #[error("package `{package_name}` has no bulk folders or files")]
+    EmptyPackage {
+        /// Name of the empty package.
+        package_name: String,
+    },
+}
+
+/// Error returned when migrating an SFDL file to another file version fails.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MigrateError {
+    /// The target version is not supported by this crate.
+    #[error("unsupported file version {0}")]
+    UnsupportedVersion(u16),
+    /// A value cannot be stored in the layout of the target version.
+    #[error("`{field}` cannot be represented in file version {version}")]
+    Unrepresentable {
+        /// Path of the field that would be lost.
+        field: &'static str,
+        /// Target version.
+        version: u16,
+    },
 }
 
 /// Top-level error type for operations on SFDL files.
 #[derive(Error, Debug)]
 pub enum SfdlError {
-    /// Encryption error.
-    #[error("encryption error")]
-    Encrypt(#[from] EncryptError),
     /// Decryption error.
     #[error("decryption error")]
     Decrypt(#[from] DecryptError),
     /// XML parsing or serialization error.
     #[error("parsing error")]
     Parse(#[from] ParseError),
+    /// File version migration error.
+    #[error("migration error")]
+    Migrate(#[from] MigrateError),
+    /// JSON, YAML or TOML conversion error.
+    #[error("format conversion error")]
+    Format(#[from] FormatError),
     /// File system I/O error.
     #[error("io error")]
     Io(#[from] std::io::Error),
@@ -0,0 +1,565 @@
+//! Preservation of unknown XML elements and attributes.
+//!
+//! Some third-party generators add their own elements or attributes to
+//! `SFDLFile`, `ConnectionInfo` or `SFDLPackage`. serde ignores everything it
+//! does not know, so this module captures the unknown parts in a second pass
+//! over the document and stores them in the [`Extensions`] of the matching
+//! model struct. Each unknown element remembers the known sibling it followed,
+//! and is emitted again right after that sibling when the container is
+//! written, so a parse and write round trip keeps it in place.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use sfdl::SfdlFile;
+//! let xml = std::fs::read_to_string("examples/decrypted.sfdl")
+//!     .unwrap()
+//!     .replace("<Uploader>", "<Generator>my-tool</Generator><Uploader>");
+//!
+//! let sfdl: SfdlFile = xml.parse().unwrap();
+//! let generator = &sfdl.extensions.elements[0];
+//! assert_eq!(generator.after.as_deref(), Some("Description"));
+//! assert_eq!(generator.xml, "<Generator>my-tool</Generator>");
+//!
+//! assert!(sfdl
+//!     .to_xml_string()
+//!     .unwrap()
+//!     .contains("</Description><Generator>my-tool</Generator><Uploader>"));
+//! ```
+
+use std::borrow::Cow;
+
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::{Reader, Writer, XmlVersion};
+
+use crate::error::ParseError;
+use crate::sfdl::{ConnectionInfo, SfdlFile};
+
+pub(crate) const SFDL_FILE_ATTRIBUTES: &[&str] = &["xmlns:xsd", "xmlns:xsi"];
+pub(crate) const SFDL_FILE_ELEMENTS: &[&str] = &[
+    "Description",
+    "Uploader",
+    "SFDLFileVersion",
+    "Encrypted",
+    "ConnectionInfo",
+    "Packages",
+    "MaxDownloadThreads",
+];
+const CONNECTION_INFO_ELEMENTS: &[&str] = &[
+    "Host",
+    "Port",
+    "Username",
+    "Password",
+    "AuthRequired",
+    "DataConnectionType",
+    "DataType",
+    "CharacterEncoding",
+    "EncryptionMode",
+    "ListMethod",
+    "DefaultPath",
+    "ForceSingleConnection",
+    "DataStaleDetection",
+    "SpecialServerMode",
+];
+pub(crate) const PACKAGE_ELEMENTS: &[&str] = &[
+    "Packagename",
+    "BulkFolderMode",
+    "BulkFolderList",
+    "FileList",
+];
+
+/// Unknown attributes and child elements of an XML element.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Extensions {
+    /// Unknown attributes as `(name, unescaped value)` pairs, in document order.
+    pub attributes: Vec<(String, String)>,
+    /// Unknown child elements, in document order.
+    pub elements: Vec<ExtensionElement>,
+}
+
+/// An unknown child element and its position among the known children.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExtensionElement {
+    /// Name of the known sibling element it follows, or `None` if it comes
+    /// before all known children.
+    ///
+    /// If that sibling is not written, the element is written after the
+    /// last known child instead.
+    pub after: Option<String>,
+    /// The element as raw XML.
+    pub xml: String,
+}
+
+impl ExtensionElement {
+    /// Creates an element written after the known sibling `after`.
+    pub fn new(after: Option<&str>, xml: impl Into<String>) -> Self {
+        Self {
+            after: after.map(str::to_string),
+            xml: xml.into(),
+        }
+    }
+}
+
+impl Extensions {
+    /// Returns `true` if there are no unknown attributes or elements.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.elements.is_empty()
+    }
+}
+
+/// The element whose children are currently being visited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Document,
+    SfdlFile,
+    ConnectionInfo,
+    Packages,
+    Package(usize),
+    Other,
+}
+
+impl Scope {
+    /// Returns the scope of a child element named `name`.
+    fn child(self, name: &[u8], package_count: &mut usize) -> Self {
+        match (self, name) {
+            (Self::Document, _) => Self::SfdlFile,
+            (Self::SfdlFile, b"ConnectionInfo") => Self::ConnectionInfo,
+            (Self::SfdlFile, b"Packages") => Self::Packages,
+            (Self::Packages, b"SFDLPackage") => {
+                *package_count += 1;
+                Self::Package(*package_count - 1)
+            }
+            _ => Self::Other,
+        }
+    }
+
+    /// Known child elements, or `None` if this scope keeps no extensions.
+    fn known_elements(self) -> Option<&'static [&'static str]> {
+        match self {
+            Self::SfdlFile => Some(SFDL_FILE_ELEMENTS),
+            Self::ConnectionInfo => Some(CONNECTION_INFO_ELEMENTS),
+            Self::Package(_) => Some(PACKAGE_ELEMENTS),
+            _ => None,
+        }
+    }
+
+    fn known_attributes(self) -> &'static [&'static str] {
+        match self {
+            Self::SfdlFile => SFDL_FILE_ATTRIBUTES,
+            _ => &[],
+        }
+    }
+
+    fn extensions(self, sfdl: &SfdlFile) -> Option<&Extensions> {
+        match self {
+            Self::SfdlFile => Some(&sfdl.extensions),
+            Self::ConnectionInfo => Some(&sfdl.connection_info.extensions),
+            Self::Package(i) => sfdl.packages.get(i).map(|package| &package.extensions),
+            _ => None,
+        }
+    }
+
+    fn extensions_mut(self, sfdl: &mut SfdlFile) -> Option<&mut Extensions> {
+        match self {
+            Self::SfdlFile => Some(&mut sfdl.extensions),
+            Self::ConnectionInfo => Some(&mut sfdl.connection_info.extensions),
+            Self::Package(i) => sfdl
+                .packages
+                .get_mut(i)
+                .map(|package| &mut package.extensions),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn is_known(known: &[&str], name: &[u8]) -> bool {
+    known.iter().any(|known| known.as_bytes() == name)
+}
+
+/// Captures the unknown attributes and elements of `xml` into `sfdl`.
+///
+/// `sfdl` must have been deserialized from the same document.
+pub(crate) fn restore(sfdl: &mut SfdlFile, xml: &str) -> Result<(), ParseError> {
+    capture(sfdl, xml, Scope::SfdlFile, Scope::extensions_mut)
+}
+
+/// Captures the unknown attributes and elements of the standalone
+/// `ConnectionInfo` document `xml` into `info`.
+pub(crate) fn restore_connection_info(
+    info: &mut ConnectionInfo,
+    xml: &str,
+) -> Result<(), ParseError> {
+    capture(info, xml, Scope::ConnectionInfo, |scope, info| {
+        (scope == Scope::ConnectionInfo).then_some(&mut info.extensions)
+    })
+}
+
+/// Captures the unknown parts of `xml`, whose root element stands for
+/// `root`, into the extensions `extensions_mut` returns for each scope.
+fn capture<T>(
+    target: &mut T,
+    xml: &str,
+    root: Scope,
+    extensions_mut: fn(Scope, &mut T) -> Option<&mut Extensions>,
+) -> Result<(), ParseError> {
+    let de_error = |err: quick_xml::Error| ParseError::InvalidSfdlDeserialize(err.into());
+
+    let mut reader = Reader::from_str(xml);
+    // Each open element with the name of its last known child.
+    let mut stack: Vec<(Scope, Option<String>)> = vec![(Scope::Document, None)];
+    let mut package_count = 0;
+
+    loop {
+        let start = usize::try_from(reader.buffer_position()).unwrap_or(usize::MAX);
+        let event = reader.read_event().map_err(de_error)?;
+        let parent = stack.last().map_or(Scope::Other, |(scope, _)| *scope);
+
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                let is_start = matches!(event, Event::Start(_));
+
+                if parent
+                    .known_elements()
+                    .is_some_and(|known| !is_known(known, element.name().as_ref()))
+                {
+                    if is_start {
+                        reader.read_to_end(element.name()).map_err(de_error)?;
+                    }
+                    let end = usize::try_from(reader.buffer_position()).unwrap_or(usize::MAX);
+                    let after = stack.last().and_then(|(_, after)| after.clone());
+                    if let Some(extensions) = extensions_mut(parent, target) {
+                        extensions.elements.push(ExtensionElement {
+                            after,
+                            xml: xml[start..end].to_string(),
+                        });
+                    }
+                    continue;
+                }
+
+                if let Some((_, after)) = stack.last_mut() {
+                    *after = Some(String::from_utf8_lossy(element.name().as_ref()).into_owned());
+                }
+                let scope = match parent {
+                    Scope::Document => root,
+                    parent => parent.child(element.name().as_ref(), &mut package_count),
+                };
+                if let Some(extensions) = extensions_mut(scope, target) {
+                    extensions
+                        .attributes
+                        .extend(unknown_attributes(element, scope.known_attributes())?);
+                }
+                if is_start {
+                    stack.push((scope, None));
+                }
+            }
+            Event::End(_) => {
+                stack.pop();
+            }
+            Event::Eof => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Returns the attributes of `element` that are not in `known`, as
+/// `(name, unescaped value)` pairs.
+pub(crate) fn unknown_attributes(
+    element: &BytesStart<'_>,
+    known: &[&str],
+) -> Result<Vec<(String, String)>, ParseError> {
+    let mut attributes = Vec::new();
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|err| ParseError::InvalidSfdlDeserialize(err.into()))?;
+        if is_known(known, attribute.key.as_ref()) {
+            continue;
+        }
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        let value = attribute
+            .normalized_value(XmlVersion::Implicit1_0)
+            .map_err(|err| ParseError::InvalidSfdlDeserialize(err.into()))?
+            .into_owned();
+        attributes.push((key, value));
+    }
+    Ok(attributes)
+}
+
+/// Writes the extensions of `sfdl` into `xml`, which must be the serde output
+/// for the same container.
+///
+/// Returns `xml` unchanged if there is nothing to add.
+pub(crate) fn inject<'a>(sfdl: &SfdlFile, xml: &'a str) -> Result<Cow<'a, str>, ParseError> {
+    let has_extensions = !sfdl.extensions.is_empty()
+        || !sfdl.connection_info.extensions.is_empty()
+        || sfdl
+            .packages
+            .iter()
+            .any(|package| !package.extensions.is_empty());
+    if !has_extensions {
+        return Ok(Cow::Borrowed(xml));
+    }
+
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::new());
+    // Each open element with its extension elements that are not written yet.
+    let mut stack: Vec<(Scope, Vec<&ExtensionElement>)> = vec![(Scope::Document, Vec::new())];
+    let mut package_count = 0;
+
+    loop {
+        let event = reader.read_event().map_err(se_error)?;
+        let parent = stack.last().map_or(Scope::Other, |(scope, _)| *scope);
+
+        match event {
+            Event::Start(element) => {
+                let scope = parent.child(element.name().as_ref(), &mut package_count);
+                let extensions = scope.extensions(sfdl);
+                let element = with_attributes(element, extensions);
+                writer
+                    .write_event(Event::Start(element))
+                    .map_err(se_io_error)?;
+                let mut pending: Vec<_> = extensions
+                    .map(|extensions| extensions.elements.iter().collect())
+                    .unwrap_or_default();
+                write_elements(&mut writer, &mut pending, |after| after.is_none())?;
+                stack.push((scope, pending));
+            }
+            Event::Empty(element) => {
+                let scope = parent.child(element.name().as_ref(), &mut package_count);
+                let name = element.name().as_ref().to_vec();
+                match scope.extensions(sfdl) {
+                    Some(extensions) if !extensions.is_empty() => {
+                        let end = BytesEnd::new(String::from_utf8_lossy(&name).into_owned());
+                        let element = with_attributes(element, Some(extensions));
+                        writer
+                            .write_event(Event::Start(element))
+                            .map_err(se_io_error)?;
+                        let mut pending = extensions.elements.iter().collect();
+                        write_elements(&mut writer, &mut pending, |_| true)?;
+                        writer.write_event(Event::End(end)).map_err(se_io_error)?;
+                    }
+                    _ => writer
+                        .write_event(Event::Empty(element))
+                        .map_err(se_io_error)?,
+                }
+                write_after(&mut writer, &mut stack, &name)?;
+            }
+            Event::End(element) => {
+                if let Some((_, mut pending)) = stack.pop() {
+                    write_elements(&mut writer, &mut pending, |_| true)?;
+                }
+                let name = element.name().as_ref().to_vec();
+                writer
+                    .write_event(Event::End(element))
+                    .map_err(se_io_error)?;
+                write_after(&mut writer, &mut stack, &name)?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event).map_err(se_io_error)?,
+        }
+    }
+
+    String::from_utf8(writer.into_inner())
+        .map(Cow::Owned)
+        .map_err(|err| ParseError::InvalidSfdlSerialize(err.utf8_error().into()))
+}
+
+fn with_attributes<'a>(element: BytesStart<'a>, extensions: Option<&Extensions>) -> BytesStart<'a> {
+    let Some(extensions) = extensions.filter(|extensions| !extensions.attributes.is_empty()) else {
+        return element;
+    };
+
+    let mut element = element.into_owned();
+    for (key, value) in &extensions.attributes {
+        element.push_attribute((key.as_str(), value.as_str()));
+    }
+    element
+}
+
+/// Writes the pending extension elements of the innermost open element that
+/// follow its known child `name`.
+fn write_after(
+    writer: &mut Writer<Vec<u8>>,
+    stack: &mut [(Scope, Vec<&ExtensionElement>)],
+    name: &[u8],
+) -> Result<(), ParseError> {
+    match stack.last_mut() {
+        Some((_, pending)) => write_elements(writer, pending, |after| {
+            after.is_some_and(|after| after.as_bytes() == name)
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Writes and removes the elements of `pending` whose `after` matches.
+fn write_elements(
+    writer: &mut Writer<Vec<u8>>,
+    pending: &mut Vec<&ExtensionElement>,
+    matches: impl Fn(Option<&str>) -> bool,
+) -> Result<(), ParseError> {
+    for element in pending.extract_if(.., |element| matches(element.after.as_deref())) {
+        let mut reader = Reader::from_str(&element.xml);
+        loop {
+            match reader.read_event().map_err(se_error)? {
+                Event::Eof => break,
+                event => writer.write_event(event).map_err(se_io_error)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn se_error(err: quick_xml::Error) -> ParseError {
+    ParseError::InvalidSfdlSerialize(quick_xml::SeError::Custom(err.to_string()))
+}
+
+fn se_io_error(err: std::io::Error) -> ParseError {
+    ParseError::InvalidSfdlSerialize(err.into())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn sample_with(replacements: &[(&str, &str)]) -> String {
+        let mut xml = std::fs::read_to_string("tests/data/mixed_mode.xml").unwrap();
+        for (from, to) in replacements {
+            assert!(xml.contains(from), "{from}");
+            xml = xml.replacen(from, to, 1);
+        }
+        xml
+    }
+
+    #[test]
+    fn test_unknown_elements_are_captured() {
+        let xml = sample_with(&[
+            ("<Uploader>", "<Generator>tool</Generator><Uploader>"),
+            (
+                "<MaxDownloadThreads>",
+                "<x:Meta xmlns:x=\"urn:x\" a=\"1\"><x:Child/></x:Meta><Tag/><MaxDownloadThreads>",
+            ),
+            (
+                "<ConnectionInfo>",
+                "<ConnectionInfo Region=\"eu &amp; us\">",
+            ),
+            (
+                "<ForceSingleConnection>",
+                "<Proxy>socks</Proxy><ForceSingleConnection>",
+            ),
+            (
+                "<BulkFolderMode>true",
+                "<Priority>high</Priority><BulkFolderMode>true",
+            ),
+        ]);
+        let sfdl: SfdlFile = xml.parse().unwrap();
+
+        assert_eq!(
+            sfdl.extensions.elements,
+            [
+                ExtensionElement::new(Some("Description"), "<Generator>tool</Generator>"),
+                ExtensionElement::new(
+                    Some("Packages"),
+                    "<x:Meta xmlns:x=\"urn:x\" a=\"1\"><x:Child/></x:Meta>"
+                ),
+                ExtensionElement::new(Some("Packages"), "<Tag/>"),
+            ]
+        );
+        assert_eq!(
+            sfdl.connection_info.extensions,
+            Extensions {
+                attributes: vec![("Region".to_string(), "eu & us".to_string())],
+                elements: vec![ExtensionElement::new(
+                    Some("DefaultPath"),
+                    "<Proxy>socks</Proxy>"
+                )],
+            }
+        );
+        assert!(sfdl.packages[0].extensions.is_empty());
+        assert_eq!(
+            sfdl.packages[1].extensions.elements,
+            [ExtensionElement::new(
+                Some("Packagename"),
+                "<Priority>high</Priority>"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unknown_elements_round_trip() {
+        let xml = sample_with(&[
+            ("<SFDLFile ", "<SFDLFile xmlns:x=\"urn:x\" "),
+            ("<Uploader>", "<x:Generator>tool</x:Generator><Uploader>"),
+            (
+                "<ConnectionInfo>",
+                "<ConnectionInfo Region=\"eu &amp; us\">",
+            ),
+            ("<ForceSingleConnection>", "<Proxy/><ForceSingleConnection>"),
+            (
+                "<Packagename>Mixed",
+                "<Note>a &lt; b</Note><Packagename>Mixed",
+            ),
+        ]);
+        let sfdl: SfdlFile = xml.parse().unwrap();
+
+        let written = sfdl.to_xml_string().unwrap();
+        assert!(written.starts_with("<SFDLFile xmlns:xsd="));
+        assert!(written.contains(" xmlns:x=\"urn:x\">"));
+        assert!(written.contains("</Description><x:Generator>tool</x:Generator><Uploader>"));
+        assert!(written.contains("<ConnectionInfo Region=\"eu &amp; us\">"));
+        assert!(written.contains("</DefaultPath><Proxy/><ForceSingleConnection>"));
+        assert!(written.contains("<SFDLPackage><Note>a &lt; b</Note><Packagename>"));
+
+        assert_eq!(written.parse::<SfdlFile>().unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_unknown_elements_keep_their_position() {
+        let xml = std::fs::read_to_string("tests/data/mixed_mode.xml").unwrap();
+        let sfdl: SfdlFile = xml.parse().unwrap();
+        let plain = sfdl.to_xml_string().unwrap();
+
+        // Insert an unknown element before every known child of `SFDLFile`.
+        let mut xml = plain.clone();
+        for name in SFDL_FILE_ELEMENTS {
+            let tag = format!("<{name}>");
+            xml = xml.replacen(&tag, &format!("<Before{name}/>{tag}"), 1);
+        }
+        xml = xml.replace("</SFDLFile>", "<Last/></SFDLFile>");
+        assert_ne!(xml, plain);
+
+        let sfdl: SfdlFile = xml.parse().unwrap();
+        assert_eq!(sfdl.extensions.elements.len(), SFDL_FILE_ELEMENTS.len() + 1);
+        assert_eq!(sfdl.to_xml_string().unwrap(), xml);
+    }
+
+    #[test]
+    fn test_missing_sibling_writes_element_last() {
+        let mut sfdl: SfdlFile = std::fs::read_to_string("tests/data/mixed_mode.xml")
+            .unwrap()
+            .parse()
+            .unwrap();
+        sfdl.packages[0]
+            .extensions
+            .elements
+            .push(ExtensionElement::new(Some("Missing"), "<Tag/>"));
+
+        let written = sfdl.to_xml_string().unwrap();
+        assert!(
+            written.contains("</FileList><Tag/></SFDLPackage>"),
+            "{written}"
+        );
+    }
+
+    #[test]
+    fn test_extensions_survive_encryption() {
+        let xml = sample_with(&[("<Uploader>", "<Generator>tool</Generator><Uploader>")]);
+        let mut sfdl: SfdlFile = xml.parse().unwrap();
+        let expected = sfdl.clone();
+
+        sfdl.encrypt("S3cr3tP4ssw0rd!").unwrap();
+        let mut reparsed: SfdlFile = sfdl.to_xml_string().unwrap().parse().unwrap();
+        reparsed.decrypt("S3cr3tP4ssw0rd!").unwrap();
+
+        assert_eq!(reparsed, expected);
+    }
+}
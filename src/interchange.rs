@@ -0,0 +1,664 @@
+//! Conversion to and from JSON, YAML and TOML, enabled by the `json`, `yaml`
+//! and `toml` features.
+//!
+//! The model types serialize with the element names and attribute
+//! conventions of the XML format. These formats use a layout of their own
+//! instead, with the Rust field names and without the XML wrapper elements:
+//!
+//! ```json
+//! {
+//!   "description": "MyDescription",
+//!   "uploader": "MyUploader",
+//!   "sfdlfile_version": 6,
+//!   "encrypted": false,
+//!   "connection_info": { "host": "ftp.example.com", "port": 21, ... },
+//!   "packages": [
+//!     {
+//!       "package_name": "MyPackage",
+//!       "bulk_folder_mode": true,
+//!       "bulk_folder_list": [{ "bulk_folder_path": "/", "package_name": "MyPackage" }]
+//!     }
+//!   ],
+//!   "max_download_threads": 3
+//! }
+//! ```
+//!
+//! `xmlns_xsd`, `xmlns_xsi` and `extensions` are only written if they differ
+//! from their defaults, and `file_list` only if the package has one. A
+//! converted container converts back into an identical [`SfdlFile`].
+
+use serde::{Serialize, Serializer};
+
+use crate::error::FormatError;
+use crate::extensions::{ExtensionElement, Extensions};
+use crate::secret::SecretString;
+use crate::sfdl::{
+    default_xmlns_xsd, default_xmlns_xsi, BulkFolder, CharacterEncoding, ConnectionInfo,
+    DataConnectionType, DataType, EncryptionMode, FileInfo, ListMethod, SfdlFile, SfdlPackage,
+};
+
+impl SfdlFile {
+    /// Serialize this SFDL file into pretty-printed JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::Json`] if serialization fails.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// let sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+    ///
+    /// let json = sfdl.to_json().unwrap();
+    /// assert_eq!(SfdlFile::from_json(&json).unwrap(), sfdl);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<String, FormatError> {
+        Ok(serde_json::to_string_pretty(&Document::from(self))?)
+    }
+
+    /// Parse a SFDL file from JSON written by [`SfdlFile::to_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::Json`] if the JSON cannot be parsed.
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, FormatError> {
+        Ok(serde_json::from_str::<owned::Document>(json)?.into())
+    }
+
+    /// Serialize this SFDL file into YAML.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::Yaml`] if serialization fails.
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml(&self) -> Result<String, FormatError> {
+        Ok(serde_yaml_ng::to_string(&Document::from(self))?)
+    }
+
+    /// Parse a SFDL file from YAML written by [`SfdlFile::to_yaml`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::Yaml`] if the YAML cannot be parsed.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(yaml: &str) -> Result<Self, FormatError> {
+        Ok(serde_yaml_ng::from_str::<owned::Document>(yaml)?.into())
+    }
+
+    /// Serialize this SFDL file into TOML.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::TomlSerialize`] if serialization fails, e.g.
+    /// for a file size TOML cannot represent.
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> Result<String, FormatError> {
+        Ok(toml::to_string(&Document::from(self))?)
+    }
+
+    /// Parse a SFDL file from TOML written by [`SfdlFile::to_toml`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormatError::TomlDeserialize`] if the TOML cannot be parsed.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, FormatError> {
+        Ok(toml::from_str::<owned::Document>(toml)?.into())
+    }
+}
+
+/// `SFDLFile` layout of the interchange formats, borrowing the container it
+/// serializes. The owned [`owned::Document`] is used for deserialization.
+#[derive(Serialize)]
+struct Document<'a> {
+    #[serde(skip_serializing_if = "is_default_xsd")]
+    xmlns_xsd: &'a str,
+    #[serde(skip_serializing_if = "is_default_xsi")]
+    xmlns_xsi: &'a str,
+    description: &'a str,
+    uploader: &'a str,
+    sfdlfile_version: u16,
+    encrypted: bool,
+    connection_info: Connection<'a>,
+    #[serde(serialize_with = "packages")]
+    packages: &'a [SfdlPackage],
+    max_download_threads: u16,
+    #[serde(skip_serializing_if = "ExtensionList::is_empty")]
+    extensions: ExtensionList<'a>,
+}
+
+/// `ConnectionInfo` layout of the interchange formats.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Serialize)]
+struct Connection<'a> {
+    host: &'a str,
+    port: u16,
+    username: &'a SecretString,
+    password: &'a SecretString,
+    auth_required: bool,
+    data_connection_type: &'a DataConnectionType,
+    data_type: &'a DataType,
+    character_encoding: &'a CharacterEncoding,
+    encryption_mode: &'a EncryptionMode,
+    list_method: &'a ListMethod,
+    default_path: &'a str,
+    force_single_connection: bool,
+    data_stale_detection: bool,
+    special_server_mode: bool,
+    #[serde(skip_serializing_if = "ExtensionList::is_empty")]
+    extensions: ExtensionList<'a>,
+}
+
+/// `SFDLPackage` layout of the interchange formats.
+#[derive(Serialize)]
+struct Package<'a> {
+    package_name: &'a str,
+    bulk_folder_mode: bool,
+    #[serde(serialize_with = "folders")]
+    bulk_folder_list: &'a [BulkFolder],
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "files")]
+    file_list: Option<&'a [FileInfo]>,
+    #[serde(skip_serializing_if = "ExtensionList::is_empty")]
+    extensions: ExtensionList<'a>,
+}
+
+/// `BulkFolder` layout of the interchange formats.
+#[derive(Serialize)]
+struct Folder<'a> {
+    bulk_folder_path: &'a str,
+    package_name: &'a str,
+}
+
+/// `FileInfo` layout of the interchange formats.
+#[derive(Serialize)]
+struct File<'a> {
+    file_name: &'a str,
+    directory_root: &'a str,
+    directory_path: &'a str,
+    file_full_path: &'a str,
+    file_size: u64,
+    file_hash_type: &'a str,
+    file_hash: &'a str,
+    package_name: &'a str,
+}
+
+/// [`Extensions`] layout of the interchange formats.
+#[derive(Serialize)]
+struct ExtensionList<'a> {
+    #[serde(skip_serializing_if = "<[_]>::is_empty", serialize_with = "attributes")]
+    attributes: &'a [(String, String)],
+    #[serde(skip_serializing_if = "<[_]>::is_empty", serialize_with = "elements")]
+    elements: &'a [ExtensionElement],
+}
+
+/// An unknown XML attribute.
+#[derive(Serialize)]
+struct Attribute<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+/// An unknown XML element.
+#[derive(Serialize)]
+struct Element<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<&'a str>,
+    xml: &'a str,
+}
+
+impl ExtensionList<'_> {
+    fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.elements.is_empty()
+    }
+}
+
+fn is_default_xsd(value: &&str) -> bool {
+    *value == default_xmlns_xsd()
+}
+
+fn is_default_xsi(value: &&str) -> bool {
+    *value == default_xmlns_xsi()
+}
+
+fn packages<S: Serializer>(packages: &&[SfdlPackage], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(packages.iter().map(Package::from))
+}
+
+fn folders<S: Serializer>(folders: &&[BulkFolder], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(folders.iter().map(|folder| Folder {
+        bulk_folder_path: &folder.bulk_folder_path,
+        package_name: &folder.package_name,
+    }))
+}
+
+fn files<S: Serializer>(files: &Option<&[FileInfo]>, serializer: S) -> Result<S::Ok, S::Error> {
+    match files {
+        Some(files) => serializer.collect_seq(files.iter().map(File::from)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn attributes<S: Serializer>(
+    attributes: &&[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        attributes
+            .iter()
+            .map(|(name, value)| Attribute { name, value }),
+    )
+}
+
+fn elements<S: Serializer>(
+    elements: &&[ExtensionElement],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(elements.iter().map(|element| Element {
+        after: element.after.as_deref(),
+        xml: &element.xml,
+    }))
+}
+
+impl<'a> From<&'a SfdlFile> for Document<'a> {
+    fn from(sfdl: &'a SfdlFile) -> Self {
+        Self {
+            xmlns_xsd: &sfdl.xmlns_xsd,
+            xmlns_xsi: &sfdl.xmlns_xsi,
+            description: &sfdl.description,
+            uploader: &sfdl.uploader,
+            sfdlfile_version: sfdl.sfdlfile_version,
+            encrypted: sfdl.encrypted,
+            connection_info: (&sfdl.connection_info).into(),
+            packages: &sfdl.packages.package,
+            max_download_threads: sfdl.max_download_threads,
+            extensions: (&sfdl.extensions).into(),
+        }
+    }
+}
+
+impl<'a> From<&'a ConnectionInfo> for Connection<'a> {
+    fn from(info: &'a ConnectionInfo) -> Self {
+        Self {
+            host: &info.host,
+            port: info.port,
+            username: &info.username,
+            password: &info.password,
+            auth_required: info.auth_required,
+            data_connection_type: &info.data_connection_type,
+            data_type: &info.data_type,
+            character_encoding: &info.character_encoding,
+            encryption_mode: &info.encryption_mode,
+            list_method: &info.list_method,
+            default_path: &info.default_path,
+            force_single_connection: info.force_single_connection,
+            data_stale_detection: info.data_stale_detection,
+            special_server_mode: info.special_server_mode,
+            extensions: (&info.extensions).into(),
+        }
+    }
+}
+
+impl<'a> From<&'a SfdlPackage> for Package<'a> {
+    fn from(package: &'a SfdlPackage) -> Self {
+        Self {
+            package_name: &package.package_name,
+            bulk_folder_mode: package.bulk_folder_mode,
+            bulk_folder_list: &package.bulk_folder_list.bulk_folder,
+            file_list: package.file_list.as_ref().map(|list| &list.file_info[..]),
+            extensions: (&package.extensions).into(),
+        }
+    }
+}
+
+impl<'a> From<&'a FileInfo> for File<'a> {
+    fn from(file: &'a FileInfo) -> Self {
+        Self {
+            file_name: &file.file_name,
+            directory_root: &file.directory_root,
+            directory_path: &file.directory_path,
+            file_full_path: &file.file_full_path,
+            file_size: file.file_size,
+            file_hash_type: &file.file_hash_type,
+            file_hash: &file.file_hash,
+            package_name: &file.package_name,
+        }
+    }
+}
+
+impl<'a> From<&'a Extensions> for ExtensionList<'a> {
+    fn from(extensions: &'a Extensions) -> Self {
+        Self {
+            attributes: &extensions.attributes,
+            elements: &extensions.elements,
+        }
+    }
+}
+
+/// Owned counterparts of the layout, for deserialization.
+mod owned {
+    use serde::Deserialize;
+
+    use crate::extensions::{ExtensionElement, Extensions};
+    use crate::secret::SecretString;
+    use crate::sfdl::{
+        default_xmlns_xsd, default_xmlns_xsi, BulkFolder, BulkFolderList, CharacterEncoding,
+        ConnectionInfo, DataConnectionType, DataType, EncryptionMode, FileInfo, FileList,
+        ListMethod, Packages, SfdlFile, SfdlPackage,
+    };
+    use crate::version::{LegacyElements, VersionFamily};
+
+    /// `SFDLFile` layout of the interchange formats.
+    #[derive(Deserialize)]
+    pub(super) struct Document {
+        #[serde(default = "default_xmlns_xsd")]
+        xmlns_xsd: String,
+        #[serde(default = "default_xmlns_xsi")]
+        xmlns_xsi: String,
+        description: String,
+        uploader: String,
+        sfdlfile_version: u16,
+        encrypted: bool,
+        connection_info: Connection,
+        packages: Vec<Package>,
+        max_download_threads: u16,
+        #[serde(default)]
+        extensions: ExtensionList,
+    }
+
+    /// `ConnectionInfo` layout of the interchange formats.
+    #[allow(clippy::struct_excessive_bools)]
+    #[derive(Deserialize)]
+    struct Connection {
+        host: String,
+        port: u16,
+        username: SecretString,
+        password: SecretString,
+        auth_required: bool,
+        data_connection_type: DataConnectionType,
+        data_type: DataType,
+        character_encoding: CharacterEncoding,
+        encryption_mode: EncryptionMode,
+        list_method: ListMethod,
+        default_path: String,
+        force_single_connection: bool,
+        data_stale_detection: bool,
+        special_server_mode: bool,
+        #[serde(default)]
+        extensions: ExtensionList,
+    }
+
+    /// `SFDLPackage` layout of the interchange formats.
+    #[derive(Deserialize)]
+    struct Package {
+        package_name: String,
+        bulk_folder_mode: bool,
+        #[serde(default)]
+        bulk_folder_list: Vec<Folder>,
+        #[serde(default)]
+        file_list: Option<Vec<File>>,
+        #[serde(default)]
+        extensions: ExtensionList,
+    }
+
+    /// `BulkFolder` layout of the interchange formats.
+    #[derive(Deserialize)]
+    struct Folder {
+        bulk_folder_path: String,
+        package_name: String,
+    }
+
+    /// `FileInfo` layout of the interchange formats.
+    #[derive(Deserialize)]
+    struct File {
+        file_name: String,
+        directory_root: String,
+        directory_path: String,
+        file_full_path: String,
+        file_size: u64,
+        file_hash_type: String,
+        file_hash: String,
+        package_name: String,
+    }
+
+    /// [`Extensions`] layout of the interchange formats.
+    #[derive(Deserialize, Default)]
+    struct ExtensionList {
+        #[serde(default)]
+        attributes: Vec<Attribute>,
+        #[serde(default)]
+        elements: Vec<Element>,
+    }
+
+    /// An unknown XML attribute.
+    #[derive(Deserialize)]
+    struct Attribute {
+        name: String,
+        value: String,
+    }
+
+    /// An unknown XML element.
+    #[derive(Deserialize)]
+    struct Element {
+        #[serde(default)]
+        after: Option<String>,
+        xml: String,
+    }
+
+    impl From<Document> for SfdlFile {
+        fn from(document: Document) -> Self {
+            let mut connection_info = ConnectionInfo::from(document.connection_info);
+            if VersionFamily::of(document.sfdlfile_version) == VersionFamily::Legacy {
+                // The interchange formats always carry both values, keep those
+                // that the legacy layout can only store as an element.
+                let defaults = ConnectionInfo::default();
+                connection_info.legacy_elements = LegacyElements {
+                    data_stale_detection: connection_info.data_stale_detection
+                        != defaults.data_stale_detection,
+                    special_server_mode: connection_info.special_server_mode
+                        != defaults.special_server_mode,
+                };
+            }
+
+            Self {
+                xmlns_xsd: document.xmlns_xsd,
+                xmlns_xsi: document.xmlns_xsi,
+                description: document.description,
+                uploader: document.uploader,
+                sfdlfile_version: document.sfdlfile_version,
+                encrypted: document.encrypted,
+                connection_info,
+                packages: Packages {
+                    package: document
+                        .packages
+                        .into_iter()
+                        .map(SfdlPackage::from)
+                        .collect(),
+                },
+                max_download_threads: document.max_download_threads,
+                extensions: document.extensions.into(),
+            }
+        }
+    }
+
+    impl From<Connection> for ConnectionInfo {
+        fn from(info: Connection) -> Self {
+            Self {
+                host: info.host,
+                port: info.port,
+                username: info.username,
+                password: info.password,
+                auth_required: info.auth_required,
+                data_connection_type: info.data_connection_type,
+                data_type: info.data_type,
+                character_encoding: info.character_encoding,
+                encryption_mode: info.encryption_mode,
+                list_method: info.list_method,
+                default_path: info.default_path,
+                force_single_connection: info.force_single_connection,
+                data_stale_detection: info.data_stale_detection,
+                special_server_mode: info.special_server_mode,
+                legacy_elements: LegacyElements::default(),
+                extensions: info.extensions.into(),
+            }
+        }
+    }
+
+    impl From<Package> for SfdlPackage {
+        fn from(package: Package) -> Self {
+            Self {
+                package_name: package.package_name,
+                bulk_folder_mode: package.bulk_folder_mode,
+                bulk_folder_list: BulkFolderList {
+                    bulk_folder: package
+                        .bulk_folder_list
+                        .into_iter()
+                        .map(|folder| BulkFolder {
+                            bulk_folder_path: folder.bulk_folder_path,
+                            package_name: folder.package_name,
+                        })
+                        .collect(),
+                },
+                file_list: package.file_list.map(|files| FileList {
+                    file_info: files.into_iter().map(FileInfo::from).collect(),
+                }),
+                extensions: package.extensions.into(),
+            }
+        }
+    }
+
+    impl From<File> for FileInfo {
+        fn from(file: File) -> Self {
+            Self {
+                file_name: file.file_name,
+                directory_root: file.directory_root,
+                directory_path: file.directory_path,
+                file_full_path: file.file_full_path,
+                file_size: file.file_size,
+                file_hash_type: file.file_hash_type,
+                file_hash: file.file_hash,
+                package_name: file.package_name,
+            }
+        }
+    }
+
+    impl From<ExtensionList> for Extensions {
+        fn from(extensions: ExtensionList) -> Self {
+            Self {
+                attributes: extensions
+                    .attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name, attribute.value))
+                    .collect(),
+                elements: extensions
+                    .elements
+                    .into_iter()
+                    .map(|element| ExtensionElement {
+                        after: element.after,
+                        xml: element.xml,
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::sfdl::FileList;
+
+    const SAMPLES: &[&str] = &[
+        "tests/data/filelist_mode.xml",
+        "tests/data/mixed_mode.xml",
+        "tests/data/multi_package_bulkfolders.xml",
+        "tests/data/legacy_v3.xml",
+        "examples/encrypted.sfdl",
+    ];
+
+    /// A container that uses every part of the layout.
+    fn sample_with_extras() -> SfdlFile {
+        let xml = std::fs::read_to_string("tests/data/mixed_mode.xml")
+            .unwrap()
+            .replace("<Uploader>", "<Generator>tool</Generator><Uploader>")
+            .replace("<DataType>Binary</DataType>", "<DataType>EBCDIC</DataType>");
+        let mut sfdl: SfdlFile = xml.parse().unwrap();
+        sfdl.xmlns_xsd = "urn:other".to_string();
+        sfdl.extensions
+            .attributes
+            .push(("source".to_string(), "a \"b\"".to_string()));
+        sfdl.packages[0].file_list = Some(FileList::default());
+        sfdl
+    }
+
+    #[test]
+    fn test_layout() {
+        let sfdl = SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap();
+        let document = Document::from(&sfdl);
+
+        assert_eq!(document.connection_info.username, "listuser");
+        let package = Package::from(&document.packages[0]);
+        assert_eq!(package.file_list.unwrap().len(), 2);
+        assert!(document.extensions.is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_round_trip() {
+        for path in SAMPLES {
+            let sfdl = SfdlFile::from_file(path).unwrap();
+            assert_eq!(SfdlFile::from_json(&sfdl.to_json().unwrap()).unwrap(), sfdl);
+        }
+
+        let sfdl = sample_with_extras();
+        let json = sfdl.to_json().unwrap();
+        assert_eq!(SfdlFile::from_json(&json).unwrap(), sfdl);
+
+        let plain = SfdlFile::from_file("tests/data/filelist_mode.xml")
+            .unwrap()
+            .to_json()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&plain).unwrap();
+        assert_eq!(value["connection_info"]["password"], "listpass");
+        assert_eq!(value["packages"][0]["file_list"][1]["file_size"], 1024);
+        assert!(value.get("xmlns_xsd").is_none());
+        assert!(value.get("extensions").is_none());
+        assert!(!plain.contains('@'));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_errors() {
+        assert!(matches!(
+            SfdlFile::from_json("{}"),
+            Err(FormatError::Json(_))
+        ));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_round_trip() {
+        for path in SAMPLES {
+            let sfdl = SfdlFile::from_file(path).unwrap();
+            assert_eq!(SfdlFile::from_yaml(&sfdl.to_yaml().unwrap()).unwrap(), sfdl);
+        }
+
+        let sfdl = sample_with_extras();
+        assert_eq!(SfdlFile::from_yaml(&sfdl.to_yaml().unwrap()).unwrap(), sfdl);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_round_trip() {
+        for path in SAMPLES {
+            let sfdl = SfdlFile::from_file(path).unwrap();
+            assert_eq!(SfdlFile::from_toml(&sfdl.to_toml().unwrap()).unwrap(), sfdl);
+        }
+
+        let sfdl = sample_with_extras();
+        assert_eq!(SfdlFile::from_toml(&sfdl.to_toml().unwrap()).unwrap(), sfdl);
+    }
+}
@@ -23,22 +23,66 @@
 //!
 //! For detailed information consult the [docs](https://docs.rs/sfdl).
 //!
+//! # Command line interface
+//!
+//! With the `cli` feature enabled the crate also builds a `sfdl` binary with
+//! `info`, `encrypt`, `decrypt`, `validate`, `convert` and `rekey` subcommands:
+//!
+//! ```bash
+//! cargo install sfdl --features cli
+//! sfdl decrypt -p "password" encrypted.sfdl -o decrypted.sfdl
+//! ```
+//!
+//! # Async I/O
+//!
+//! The `tokio` feature adds `SfdlFile::from_async_reader`,
+//! `SfdlFile::from_file_async`, `SfdlFile::to_async_writer` and
+//! `SfdlFile::write_async`. They parse and serialize exactly like their
+//! blocking counterparts.
+//!
+//! # Streaming
+//!
+//! For containers with very many file entries, [`stream::SfdlReader`] yields
+//! the header, the connection settings, and then every package and `FileInfo`
+//! entry one at a time instead of building the whole `SfdlFile`. Given a
+//! password, it decrypts each item as it is read.
+//!
+//! # JSON, YAML and TOML
+//!
+//! The `json`, `yaml` and `toml` features add `SfdlFile::to_json`,
+//! `SfdlFile::from_json` and their YAML and TOML counterparts. These formats use
+//! the Rust field names instead of the XML element names, and a converted
+//! container converts back into an identical `SfdlFile`. The CLI converts with
+//! `sfdl convert --to json`.
+//!
+//! # Schemas
+//!
+//! The `schema` module generates an XML Schema (`schema::xsd`) and a JSON Schema
+//! (`schema::json_schema`) of the format, also published in the `schema`
+//! directory. `SfdlFile::validate_schema` checks a raw document against the
+//! schema and reports every missing, duplicate or unexpected element and
+//! invalid value before deserializing.
+//!
 //! # Encrypted fields
 //!
-//! The crate follows the official `n0ix/SFDL.Container` reference implementation
-//! and only encrypts/decrypts the fields the reference handles:
+//! The crate follows the official reference implementations and only
+//! encrypts/decrypts the fields they handle:
 //!
 //! - `SFDLFile.Description`
 //! - `SFDLFile.Uploader`
 //! - `ConnectionInfo.Host`
 //! - `ConnectionInfo.Password`
 //! - `ConnectionInfo.Username`
-//! - `ConnectionInfo.DefaultPath`
 //! - Every `SFDLPackage.Packagename`
 //! - Every `BulkFolder.BulkFolderPath` and `BulkFolder.PackageName`
 //! - Every `FileInfo.DirectoryPath`, `DirectoryRoot`, `FileName`,
 //!   `FileFullPath`, and `PackageName` for file-list packages
 //!
+//! `ConnectionInfo.DefaultPath` is encrypted by SFDL.NET but left plaintext by
+//! `n0ix/SFDL.Container`. [`EncryptionProfile`] selects the behavior when
+//! encrypting; the default follows SFDL.NET. When decrypting, `DefaultPath` is
+//! detected per file, so containers of both kinds can be read.
+//!
 //! # Cryptographic primitive
 //!
 //! The SFDL format uses the following primitive, which this crate implements
@@ -49,6 +93,9 @@
 //! - A fresh 16-byte IV per field, prepended to the ciphertext.
 //! - IV + ciphertext encoded with standard base64.
 //!
+//! Single values can be encrypted, decrypted and verified with the
+//! [`crypto`] module.
+//!
 //! # Security limitations
 //!
 //! The format itself has weaknesses that this crate cannot fix:
@@ -57,6 +104,10 @@
 //! - No integrity check (MAC/HMAC/AEAD) is performed, so encrypted containers
 //!   are malleable.
 //!
+//! Enable the `zeroize` feature to wipe derived keys, intermediate plaintext
+//! buffers and the connection credentials, stored as [`SecretString`], from
+//! memory when they are dropped.
+//!
 //! Encryption and decryption are performed atomically:
 //! [`SfdlFile::encrypt`](crate::SfdlFile::encrypt) and
 //! [`SfdlFile::decrypt`](crate::SfdlFile::decrypt) only replace the caller's
@@ -69,13 +120,41 @@
 //! - [SFDL.NET](https://github.com/n0ix/SFDL.NET)
 //! - [SFDL.Container .NET implementation](https://github.com/n0ix/SFDL.Container)
 
+#[cfg(feature = "tokio")]
+mod async_io;
+pub mod builder;
+pub mod encoding;
 pub mod error;
+pub mod extensions;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+mod interchange;
+pub mod iv;
+pub mod schema;
+pub mod secret;
 pub mod sfdl;
+pub mod stream;
+pub mod validation;
+pub mod version;
+pub mod writer;
+
+pub mod crypto;
 
-mod crypto;
+pub use encoding_rs;
 
-pub use crate::error::{DecryptError, EncryptError, ParseError, SfdlError};
+pub use crate::builder::{ConnectionInfoBuilder, PackageBuilder, SfdlFileBuilder};
+pub use crate::crypto::{
+    CandidateMatch, DecryptReport, EncryptionProfile, EncryptionState, SfdlKey,
+};
+pub use crate::encoding::SourceEncoding;
+pub use crate::error::{
+    BuildError, DecryptError, FormatError, MigrateError, ParseError, SfdlError,
+};
+pub use crate::extensions::{ExtensionElement, Extensions};
+pub use crate::iv::{FixedIvSource, IvSource, OsIvSource, SeededIvSource};
+pub use crate::secret::SecretString;
 pub use crate::sfdl::{
     BulkFolder, BulkFolderList, CharacterEncoding, ConnectionInfo, DataConnectionType, DataType,
-    EncryptionMode, FileInfo, FileList, Packages, SfdlFile, SfdlPackage,
+    EncryptionMode, FileInfo, FileList, ListMethod, Packages, Redacted, SfdlFile, SfdlPackage,
 };
+pub use crate::validation::{Diagnostic, DiagnosticCode, Severity};
+pub use crate::writer::{LineEnding, WriteOptions};
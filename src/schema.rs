@@ -0,0 +1,1427 @@
+//! Machine-readable schemas of the SFDL format.
+//!
+//! The element names of the format are not always obvious: a package has a
+//! `Packagename`, while its bulk folders and files have a `PackageName`. This
+//! module describes every element of the model once and derives from that
+//! description:
+//!
+//! - [`xsd`], an XML Schema of the XML layout,
+//! - [`json_schema`], a JSON Schema of the layout written by
+//!   `SfdlFile::to_json`,
+//! - [`SfdlFile::validate_schema`], which checks a raw document element by
+//!   element before it is deserialized.
+//!
+//! Both schemas are also published in the `schema` directory of the
+//! repository.
+//!
+//! The children of `SFDLFile`, `ConnectionInfo` and `SFDLPackage` may appear in
+//! any order. `DataStaleDetection` and `SpecialServerMode` are required from
+//! file version 6 on. The schemas list the known values of the enumerated
+//! settings, such as `DataType`, and do not allow unknown elements or JSON
+//! properties. The crate itself is more lenient and keeps unknown values and
+//! elements, so [`SfdlFile::validate_schema`] reports them as warnings.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use sfdl::SfdlFile;
+//! # use sfdl::validation::{DiagnosticCode, Severity};
+//! let xml = std::fs::read_to_string("examples/decrypted.sfdl")
+//!     .unwrap()
+//!     .replace("<Port>21</Port>", "<Port>ftp</Port>");
+//!
+//! let diagnostics = SfdlFile::validate_schema(&xml);
+//! assert_eq!(diagnostics.len(), 1);
+//! assert_eq!(diagnostics[0].code, DiagnosticCode::InvalidValue);
+//! assert_eq!(diagnostics[0].path, "/SFDLFile/ConnectionInfo/Port");
+//! ```
+
+use std::fmt::Write as _;
+
+use quick_xml::errors::IllFormedError;
+use quick_xml::escape::{escape, resolve_predefined_entity};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::encoding;
+use crate::sfdl::{
+    CharacterEncoding, DataConnectionType, DataType, EncryptionMode, ListMethod, SfdlFile,
+};
+use crate::validation::{Diagnostic, DiagnosticCode, Severity};
+use crate::version::{self, CURRENT_VERSION};
+
+/// An element of the format.
+#[derive(Clone, Copy)]
+struct Element {
+    /// Name in the XML layout.
+    name: &'static str,
+    /// Name in the JSON layout, which is the field name in the model.
+    field: &'static str,
+    description: &'static str,
+    content: Content,
+    occurs: Occurs,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Occurs {
+    Required,
+    Optional,
+    /// Required from the given file version on, optional before.
+    RequiredSince(u16),
+}
+
+#[derive(Clone, Copy)]
+enum Content {
+    String,
+    Boolean,
+    UnsignedShort,
+    UnsignedLong,
+    /// One of the known values of a lenient enum, compared case-insensitively.
+    Enumeration(&'static [&'static str]),
+    /// Child elements in any order. Unknown children of an `extensible`
+    /// element are kept as extensions.
+    Record {
+        children: &'static [Element],
+        extensible: bool,
+    },
+    /// Any number of `item` elements, but at least `min`.
+    List {
+        item: &'static Element,
+        min: usize,
+    },
+}
+
+impl Element {
+    const fn new(
+        name: &'static str,
+        field: &'static str,
+        description: &'static str,
+        content: Content,
+    ) -> Self {
+        Self {
+            name,
+            field,
+            description,
+            content,
+            occurs: Occurs::Required,
+        }
+    }
+
+    const fn occurs(self, occurs: Occurs) -> Self {
+        Self { occurs, ..self }
+    }
+
+    fn is_required(&self, version: u16) -> bool {
+        match self.occurs {
+            Occurs::Required => true,
+            Occurs::Optional => false,
+            Occurs::RequiredSince(since) => version >= since,
+        }
+    }
+
+    fn documentation(&self) -> String {
+        match self.occurs {
+            Occurs::RequiredSince(since) => {
+                format!(
+                    "{} Required from file version {since} on.",
+                    self.description
+                )
+            }
+            _ => self.description.to_string(),
+        }
+    }
+}
+
+impl Content {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Boolean => "`true`, `false`, `1` or `0`",
+            Self::UnsignedShort => "a number from 0 to 65535",
+            Self::UnsignedLong => "a number from 0 to 18446744073709551615",
+            _ => "text",
+        }
+    }
+}
+
+const SFDL_FILE: Element = Element::new(
+    "SFDLFile",
+    "",
+    "An SFDL container.",
+    Content::Record {
+        children: SFDL_FILE_CHILDREN,
+        extensible: true,
+    },
+);
+
+const SFDL_FILE_CHILDREN: &[Element] = &[
+    Element::new(
+        "Description",
+        "description",
+        "Description of the SFDL container.",
+        Content::String,
+    ),
+    Element::new(
+        "Uploader",
+        "uploader",
+        "Uploader of the SFDL container.",
+        Content::String,
+    ),
+    Element::new(
+        "SFDLFileVersion",
+        "sfdlfile_version",
+        "File format version.",
+        Content::UnsignedShort,
+    ),
+    Element::new(
+        "Encrypted",
+        "encrypted",
+        "Whether the encryptable fields are encrypted.",
+        Content::Boolean,
+    ),
+    Element::new(
+        "ConnectionInfo",
+        "connection_info",
+        "Connection settings for the target server.",
+        Content::Record {
+            children: CONNECTION_INFO_CHILDREN,
+            extensible: true,
+        },
+    ),
+    Element::new(
+        "Packages",
+        "packages",
+        "List of packages contained in this SFDL file.",
+        Content::List {
+            item: &PACKAGE,
+            min: 1,
+        },
+    ),
+    Element::new(
+        "MaxDownloadThreads",
+        "max_download_threads",
+        "Maximum number of concurrent download threads.",
+        Content::UnsignedShort,
+    ),
+];
+
+const CONNECTION_INFO_CHILDREN: &[Element] = &[
+    Element::new(
+        "Host",
+        "host",
+        "Server hostname or IP address.",
+        Content::String,
+    ),
+    Element::new("Port", "port", "Server port.", Content::UnsignedShort),
+    Element::new(
+        "Username",
+        "username",
+        "Username for authentication.",
+        Content::String,
+    ),
+    Element::new(
+        "Password",
+        "password",
+        "Password for authentication.",
+        Content::String,
+    ),
+    Element::new(
+        "AuthRequired",
+        "auth_required",
+        "Whether authentication is required.",
+        Content::Boolean,
+    ),
+    Element::new(
+        "DataConnectionType",
+        "data_connection_type",
+        "FTP data connection mode.",
+        Content::Enumeration(DataConnectionType::VALUES),
+    ),
+    Element::new(
+        "DataType",
+        "data_type",
+        "Transfer data type.",
+        Content::Enumeration(DataType::VALUES),
+    ),
+    Element::new(
+        "CharacterEncoding",
+        "character_encoding",
+        "Character encoding used by the server.",
+        Content::Enumeration(CharacterEncoding::VALUES),
+    ),
+    Element::new(
+        "EncryptionMode",
+        "encryption_mode",
+        "Encryption mode for the connection.",
+        Content::Enumeration(EncryptionMode::VALUES),
+    ),
+    Element::new(
+        "ListMethod",
+        "list_method",
+        "Method used for listing directory contents.",
+        Content::Enumeration(ListMethod::VALUES),
+    ),
+    Element::new(
+        "DefaultPath",
+        "default_path",
+        "Default path on the server.",
+        Content::String,
+    ),
+    Element::new(
+        "ForceSingleConnection",
+        "force_single_connection",
+        "Whether to force a single connection.",
+        Content::Boolean,
+    ),
+    Element::new(
+        "DataStaleDetection",
+        "data_stale_detection",
+        "Whether stale data detection is enabled.",
+        Content::Boolean,
+    )
+    .occurs(Occurs::RequiredSince(CURRENT_VERSION)),
+    Element::new(
+        "SpecialServerMode",
+        "special_server_mode",
+        "Whether special server compatibility mode is enabled.",
+        Content::Boolean,
+    )
+    .occurs(Occurs::RequiredSince(CURRENT_VERSION)),
+];
+
+const PACKAGE: Element = Element::new(
+    "SFDLPackage",
+    "",
+    "A single download package.",
+    Content::Record {
+        children: PACKAGE_CHILDREN,
+        extensible: true,
+    },
+);
+
+const PACKAGE_CHILDREN: &[Element] = &[
+    Element::new(
+        "Packagename",
+        "package_name",
+        "Name of the package. Unlike `PackageName` of bulk folders and files, it is written with a lowercase `n`.",
+        Content::String,
+    ),
+    Element::new(
+        "BulkFolderMode",
+        "bulk_folder_mode",
+        "Whether this package uses bulk-folder mode.",
+        Content::Boolean,
+    ),
+    Element::new(
+        "BulkFolderList",
+        "bulk_folder_list",
+        "Folders to download in bulk-folder mode.",
+        Content::List {
+            item: &BULK_FOLDER,
+            min: 0,
+        },
+    )
+    .occurs(Occurs::Optional),
+    Element::new(
+        "FileList",
+        "file_list",
+        "Files to download when not in bulk-folder mode.",
+        Content::List {
+            item: &FILE_INFO,
+            min: 0,
+        },
+    )
+    .occurs(Occurs::Optional),
+];
+
+const BULK_FOLDER: Element = Element::new(
+    "BulkFolder",
+    "",
+    "A folder downloaded as a whole.",
+    Content::Record {
+        children: &[
+            Element::new(
+                "BulkFolderPath",
+                "bulk_folder_path",
+                "Path of the bulk folder on the server.",
+                Content::String,
+            ),
+            Element::new(
+                "PackageName",
+                "package_name",
+                "Package name associated with this bulk folder.",
+                Content::String,
+            ),
+        ],
+        extensible: false,
+    },
+);
+
+const FILE_INFO: Element = Element::new(
+    "FileInfo",
+    "",
+    "A single file to download.",
+    Content::Record {
+        children: &[
+            Element::new(
+                "FileName",
+                "file_name",
+                "Name of the file.",
+                Content::String,
+            ),
+            Element::new(
+                "DirectoryRoot",
+                "directory_root",
+                "Root directory of the file.",
+                Content::String,
+            ),
+            Element::new(
+                "DirectoryPath",
+                "directory_path",
+                "Directory path containing the file.",
+                Content::String,
+            ),
+            Element::new(
+                "FileFullPath",
+                "file_full_path",
+                "Full path of the file on the server.",
+                Content::String,
+            ),
+            Element::new(
+                "FileSize",
+                "file_size",
+                "Size of the file in bytes.",
+                Content::UnsignedLong,
+            ),
+            Element::new(
+                "FileHashType",
+                "file_hash_type",
+                "Type of hash used for the file.",
+                Content::String,
+            ),
+            Element::new(
+                "FileHash",
+                "file_hash",
+                "Hash value of the file.",
+                Content::String,
+            ),
+            Element::new(
+                "PackageName",
+                "package_name",
+                "Package name this file belongs to.",
+                Content::String,
+            ),
+        ],
+        extensible: false,
+    },
+);
+
+/// Returns an XML Schema (XSD) of the XML layout.
+///
+/// # Example
+///
+/// ```rust
+/// let xsd = sfdl::schema::xsd();
+/// assert!(xsd.contains(r#"<xs:element name="Packagename" type="xs:string">"#));
+/// ```
+#[must_use]
+pub fn xsd() -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <xs:schema xmlns:xs=\"http://www.w3.org/2001/XMLSchema\">\n",
+    );
+    xsd_element(&mut out, &SFDL_FILE, 1, "");
+    out.push_str("</xs:schema>\n");
+    out
+}
+
+fn xsd_element(out: &mut String, element: &Element, depth: usize, occurs: &str) {
+    let xsd_type = match element.content {
+        Content::String => " type=\"xs:string\"",
+        Content::Boolean => " type=\"xs:boolean\"",
+        Content::UnsignedShort => " type=\"xs:unsignedShort\"",
+        Content::UnsignedLong => " type=\"xs:unsignedLong\"",
+        _ => "",
+    };
+    line(
+        out,
+        depth,
+        &format!("<xs:element name=\"{}\"{occurs}{xsd_type}>", element.name),
+    );
+    line(out, depth + 1, "<xs:annotation>");
+    line(
+        out,
+        depth + 2,
+        &format!(
+            "<xs:documentation>{}</xs:documentation>",
+            escape(element.documentation())
+        ),
+    );
+    line(out, depth + 1, "</xs:annotation>");
+
+    match element.content {
+        Content::Enumeration(values) => {
+            line(out, depth + 1, "<xs:simpleType>");
+            line(out, depth + 2, "<xs:restriction base=\"xs:string\">");
+            for value in values {
+                line(
+                    out,
+                    depth + 3,
+                    &format!("<xs:enumeration value=\"{value}\"/>"),
+                );
+            }
+            line(out, depth + 2, "</xs:restriction>");
+            line(out, depth + 1, "</xs:simpleType>");
+        }
+        Content::Record { children, .. } => {
+            line(out, depth + 1, "<xs:complexType>");
+            line(out, depth + 2, "<xs:all>");
+            for child in children {
+                let occurs = if child.occurs == Occurs::Required {
+                    ""
+                } else {
+                    " minOccurs=\"0\""
+                };
+                xsd_element(out, child, depth + 3, occurs);
+            }
+            line(out, depth + 2, "</xs:all>");
+            line(out, depth + 1, "</xs:complexType>");
+        }
+        Content::List { item, min } => {
+            line(out, depth + 1, "<xs:complexType>");
+            line(out, depth + 2, "<xs:sequence>");
+            let occurs = format!(" minOccurs=\"{min}\" maxOccurs=\"unbounded\"");
+            xsd_element(out, item, depth + 3, &occurs);
+            line(out, depth + 2, "</xs:sequence>");
+            line(out, depth + 1, "</xs:complexType>");
+        }
+        _ => {}
+    }
+
+    line(out, depth, "</xs:element>");
+}
+
+fn line(out: &mut String, depth: usize, text: &str) {
+    out.extend(std::iter::repeat_n("  ", depth));
+    out.push_str(text);
+    out.push('\n');
+}
+
+/// Returns a JSON Schema (draft 2020-12) of the layout written by
+/// `SfdlFile::to_json`.
+///
+/// # Example
+///
+/// ```rust
+/// let schema = sfdl::schema::json_schema();
+/// assert!(schema.contains(r#""package_name": {"#));
+/// ```
+#[must_use]
+pub fn json_schema() -> String {
+    let namespace = |description: &str| {
+        Json::Object(vec![
+            ("description", Json::from(description)),
+            ("type", Json::from("string")),
+        ])
+    };
+    let (children, required) = json_record(SFDL_FILE_CHILDREN, true);
+    let mut properties = vec![
+        (
+            "xmlns_xsd",
+            namespace("XML namespace for XML Schema, if not the default."),
+        ),
+        (
+            "xmlns_xsi",
+            namespace("XML namespace for XML Schema instance, if not the default."),
+        ),
+    ];
+    properties.extend(children);
+
+    let attribute = Json::Object(vec![
+        ("type", Json::from("object")),
+        (
+            "properties",
+            Json::Object(vec![
+                ("name", Json::Object(vec![("type", Json::from("string"))])),
+                ("value", Json::Object(vec![("type", Json::from("string"))])),
+            ]),
+        ),
+        (
+            "required",
+            Json::Array(vec![Json::from("name"), Json::from("value")]),
+        ),
+        ("additionalProperties", Json::Bool(false)),
+    ]);
+    let element = Json::Object(vec![
+        ("type", Json::from("object")),
+        (
+            "properties",
+            Json::Object(vec![
+                (
+                    "after",
+                    Json::Object(vec![
+                        (
+                            "description",
+                            Json::from("Name of the known sibling element it follows."),
+                        ),
+                        ("type", Json::from("string")),
+                    ]),
+                ),
+                ("xml", Json::Object(vec![("type", Json::from("string"))])),
+            ]),
+        ),
+        ("required", Json::Array(vec![Json::from("xml")])),
+        ("additionalProperties", Json::Bool(false)),
+    ]);
+    let extensions = Json::Object(vec![
+        (
+            "description",
+            Json::from("Unknown XML attributes and child elements, kept for round trips."),
+        ),
+        ("type", Json::from("object")),
+        (
+            "properties",
+            Json::Object(vec![
+                (
+                    "attributes",
+                    Json::Object(vec![("type", Json::from("array")), ("items", attribute)]),
+                ),
+                (
+                    "elements",
+                    Json::Object(vec![("type", Json::from("array")), ("items", element)]),
+                ),
+            ]),
+        ),
+        ("additionalProperties", Json::Bool(false)),
+    ]);
+
+    let root = Json::Object(vec![
+        (
+            "$schema",
+            Json::from("https://json-schema.org/draft/2020-12/schema"),
+        ),
+        ("title", Json::from("SFDL container")),
+        ("description", Json::from(SFDL_FILE.documentation())),
+        ("type", Json::from("object")),
+        ("properties", Json::Object(properties)),
+        ("required", Json::Array(required)),
+        ("additionalProperties", Json::Bool(false)),
+        ("$defs", Json::Object(vec![("extensions", extensions)])),
+    ]);
+
+    let mut out = String::new();
+    root.write(&mut out, 0);
+    out.push('\n');
+    out
+}
+
+fn json_element(element: &Element) -> Json {
+    let mut schema = vec![("description", Json::from(element.documentation()))];
+
+    match element.content {
+        Content::String => schema.push(("type", Json::from("string"))),
+        Content::Boolean => schema.push(("type", Json::from("boolean"))),
+        Content::UnsignedShort => schema.extend([
+            ("type", Json::from("integer")),
+            ("minimum", Json::Number(0)),
+            ("maximum", Json::Number(u16::MAX.into())),
+        ]),
+        Content::UnsignedLong => schema.extend([
+            ("type", Json::from("integer")),
+            ("minimum", Json::Number(0)),
+            ("maximum", Json::Number(u64::MAX)),
+        ]),
+        Content::Enumeration(values) => schema.extend([
+            ("type", Json::from("string")),
+            (
+                "enum",
+                Json::Array(values.iter().map(|&value| Json::from(value)).collect()),
+            ),
+        ]),
+        Content::Record {
+            children,
+            extensible,
+        } => {
+            let (properties, required) = json_record(children, extensible);
+            schema.extend([
+                ("type", Json::from("object")),
+                ("properties", Json::Object(properties)),
+                ("required", Json::Array(required)),
+                ("additionalProperties", Json::Bool(false)),
+            ]);
+        }
+        Content::List { item, min } => {
+            schema.extend([("type", Json::from("array")), ("items", json_element(item))]);
+            if min > 0 {
+                schema.push(("minItems", Json::Number(min as u64)));
+            }
+        }
+    }
+
+    Json::Object(schema)
+}
+
+/// Returns the properties of a record and the names of the required ones.
+fn json_record(children: &[Element], extensible: bool) -> (Vec<(&'static str, Json)>, Vec<Json>) {
+    let mut properties: Vec<_> = children
+        .iter()
+        .map(|child| (child.field, json_element(child)))
+        .collect();
+    if extensible {
+        properties.push((
+            "extensions",
+            Json::Object(vec![("$ref", Json::from("#/$defs/extensions"))]),
+        ));
+    }
+    let required = children
+        .iter()
+        .filter(|child| child.occurs != Occurs::Optional)
+        .map(|child| Json::from(child.field))
+        .collect();
+
+    (properties, required)
+}
+
+/// A JSON value with the keys of objects kept in order.
+enum Json {
+    Bool(bool),
+    String(String),
+    Number(u64),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl Json {
+    /// Writes the value pretty-printed with two spaces per level.
+    fn write(&self, out: &mut String, depth: usize) {
+        match self {
+            Self::Bool(value) => {
+                let _ = write!(out, "{value}");
+            }
+            Self::String(value) => write_json_string(out, value),
+            Self::Number(value) => {
+                let _ = write!(out, "{value}");
+            }
+            Self::Array(items) if items.iter().all(|item| matches!(item, Self::String(_))) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    item.write(out, depth);
+                }
+                out.push(']');
+            }
+            Self::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(if i > 0 { ",\n" } else { "\n" });
+                    out.extend(std::iter::repeat_n("  ", depth + 1));
+                    item.write(out, depth + 1);
+                }
+                out.push('\n');
+                out.extend(std::iter::repeat_n("  ", depth));
+                out.push(']');
+            }
+            Self::Object(entries) => {
+                out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    out.push_str(if i > 0 { ",\n" } else { "\n" });
+                    out.extend(std::iter::repeat_n("  ", depth + 1));
+                    write_json_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, depth + 1);
+                }
+                out.push('\n');
+                out.extend(std::iter::repeat_n("  ", depth));
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The error for a document that ends inside the element at `path`.
+fn unexpected_eof(path: &str) -> quick_xml::Error {
+    let segment = path.rsplit('/').next().unwrap_or_default();
+    let name = segment.split('[').next().unwrap_or_default();
+    IllFormedError::MissingEndTag(name.to_string()).into()
+}
+
+impl SfdlFile {
+    /// Check a raw document against the schema of the format and return
+    /// every violation found.
+    ///
+    /// Unlike parsing, this does not stop at the first problem and does not
+    /// need the document to deserialize. Missing, duplicate and misplaced
+    /// elements and invalid values are reported as errors, unknown elements
+    /// and unknown enumeration values as warnings. The text encoding is
+    /// detected as in [`SfdlFile::from_bytes`]. See the
+    /// [`schema`](crate::schema) module for details.
+    #[must_use]
+    pub fn validate_schema(xml: impl AsRef<[u8]>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        match encoding::decode(xml.as_ref()) {
+            Ok((text, _)) => {
+                let mut checker = Checker {
+                    reader: Reader::from_str(&text),
+                    version: version::detect_version(&text).unwrap_or(CURRENT_VERSION),
+                    diagnostics: Vec::new(),
+                };
+                checker.document();
+                diagnostics = checker.diagnostics;
+            }
+            Err(err) => diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: DiagnosticCode::MalformedXml,
+                path: "/".to_string(),
+                message: err.to_string(),
+            }),
+        }
+        diagnostics
+    }
+}
+
+/// Walks a document and collects schema violations.
+struct Checker<'a> {
+    reader: Reader<&'a [u8]>,
+    /// File version of the document, which decides the required elements.
+    version: u16,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: DiagnosticCode,
+        path: String,
+        message: impl Into<String>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            path,
+            message: message.into(),
+        });
+    }
+
+    fn document(&mut self) {
+        if let Err(err) = self.root() {
+            let message = match err {
+                quick_xml::Error::IllFormed(IllFormedError::MissingEndTag(name)) => {
+                    format!("unexpected end of document, `</{name}>` is missing")
+                }
+                err => format!("{err} at byte {}", self.reader.error_position()),
+            };
+            self.push(
+                Severity::Error,
+                DiagnosticCode::MalformedXml,
+                "/".to_string(),
+                message,
+            );
+        }
+    }
+
+    fn root(&mut self) -> Result<(), quick_xml::Error> {
+        loop {
+            let (start, empty) = match self.reader.read_event()? {
+                Event::Start(start) => (start, false),
+                Event::Empty(start) => (start, true),
+                Event::Eof => {
+                    self.push(
+                        Severity::Error,
+                        DiagnosticCode::MissingElement,
+                        format!("/{}", SFDL_FILE.name),
+                        "document has no root element",
+                    );
+                    return Ok(());
+                }
+                Event::Text(text) if text.iter().all(u8::is_ascii_whitespace) => continue,
+                Event::Text(_) | Event::CData(_) | Event::GeneralRef(_) => {
+                    self.push(
+                        Severity::Error,
+                        DiagnosticCode::MalformedXml,
+                        "/".to_string(),
+                        "text before the root element",
+                    );
+                    return Ok(());
+                }
+                _ => continue,
+            };
+
+            let name = element_name(&start);
+            if name != SFDL_FILE.name {
+                self.push(
+                    Severity::Error,
+                    DiagnosticCode::UnexpectedElement,
+                    format!("/{name}"),
+                    format!("expected root element `{}`", SFDL_FILE.name),
+                );
+                return Ok(());
+            }
+            return self.element(&SFDL_FILE, &format!("/{name}"), empty);
+        }
+    }
+
+    fn element(
+        &mut self,
+        element: &Element,
+        path: &str,
+        empty: bool,
+    ) -> Result<(), quick_xml::Error> {
+        match element.content {
+            Content::Record {
+                children,
+                extensible,
+            } => self.record(children, extensible, path, empty),
+            Content::List { item, min } => self.list(item, min, path, empty),
+            content => {
+                let text = self.text(element, path, empty)?;
+                self.value(element.name, content, path, &text);
+                Ok(())
+            }
+        }
+    }
+
+    fn record(
+        &mut self,
+        children: &[Element],
+        extensible: bool,
+        path: &str,
+        empty: bool,
+    ) -> Result<(), quick_xml::Error> {
+        let mut counts = vec![0usize; children.len()];
+
+        if !empty {
+            loop {
+                let (start, empty) = match self.reader.read_event()? {
+                    Event::Start(start) => (start, false),
+                    Event::Empty(start) => (start, true),
+                    Event::End(_) => break,
+                    Event::Eof => return Err(unexpected_eof(path)),
+                    _ => continue,
+                };
+
+                let name = element_name(&start);
+                let Some(i) = children.iter().position(|child| child.name == name) else {
+                    let message = if extensible {
+                        "unknown element is kept as an extension"
+                    } else {
+                        "unknown element is ignored"
+                    };
+                    self.push(
+                        Severity::Warning,
+                        DiagnosticCode::UnexpectedElement,
+                        format!("{path}/{name}"),
+                        message,
+                    );
+                    self.skip(&start, empty)?;
+                    continue;
+                };
+
+                counts[i] += 1;
+                let child_path = format!("{path}/{name}");
+                if counts[i] == 2 {
+                    self.push(
+                        Severity::Error,
+                        DiagnosticCode::DuplicateElement,
+                        child_path.clone(),
+                        "element may appear only once",
+                    );
+                }
+                self.element(&children[i], &child_path, empty)?;
+            }
+        }
+
+        for (child, count) in children.iter().zip(counts) {
+            if count == 0 && child.is_required(self.version) {
+                self.push(
+                    Severity::Error,
+                    DiagnosticCode::MissingElement,
+                    format!("{path}/{}", child.name),
+                    "required element is missing",
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn list(
+        &mut self,
+        item: &Element,
+        min: usize,
+        path: &str,
+        empty: bool,
+    ) -> Result<(), quick_xml::Error> {
+        let mut count = 0;
+
+        if !empty {
+            loop {
+                let (start, empty) = match self.reader.read_event()? {
+                    Event::Start(start) => (start, false),
+                    Event::Empty(start) => (start, true),
+                    Event::End(_) => break,
+                    Event::Eof => return Err(unexpected_eof(path)),
+                    _ => continue,
+                };
+
+                let name = element_name(&start);
+                if name == item.name {
+                    count += 1;
+                    self.element(item, &format!("{path}/{name}[{count}]"), empty)?;
+                } else {
+                    self.push(
+                        Severity::Warning,
+                        DiagnosticCode::UnexpectedElement,
+                        format!("{path}/{name}"),
+                        format!("unknown element is ignored, expected `{}`", item.name),
+                    );
+                    self.skip(&start, empty)?;
+                }
+            }
+        }
+
+        if count < min {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::MissingElement,
+                format!("{path}/{}", item.name),
+                format!("at least {min} `{}` element is required", item.name),
+            );
+        }
+        Ok(())
+    }
+
+    /// Reads the text content of a simple element.
+    fn text(
+        &mut self,
+        element: &Element,
+        path: &str,
+        empty: bool,
+    ) -> Result<String, quick_xml::Error> {
+        let mut text = String::new();
+
+        if !empty {
+            loop {
+                match self.reader.read_event()? {
+                    Event::Text(content) => text.push_str(&content.decode()?),
+                    Event::CData(content) => text.push_str(&content.decode()?),
+                    Event::GeneralRef(reference) => {
+                        if let Some(c) = reference.resolve_char_ref()? {
+                            text.push(c);
+                        } else {
+                            let name = reference.decode()?;
+                            match resolve_predefined_entity(&name) {
+                                Some(value) => text.push_str(value),
+                                None => {
+                                    text.push('&');
+                                    text.push_str(&name);
+                                    text.push(';');
+                                }
+                            }
+                        }
+                    }
+                    Event::Start(start) => {
+                        self.nested(element, path, &start);
+                        self.skip(&start, false)?;
+                    }
+                    Event::Empty(start) => self.nested(element, path, &start),
+                    Event::End(_) => break,
+                    Event::Eof => return Err(unexpected_eof(path)),
+                    _ => {}
+                }
+            }
+        }
+        Ok(text)
+    }
+
+    fn nested(&mut self, element: &Element, path: &str, start: &BytesStart<'_>) {
+        self.push(
+            Severity::Error,
+            DiagnosticCode::UnexpectedElement,
+            format!("{path}/{}", element_name(start)),
+            format!("`{}` cannot contain elements", element.name),
+        );
+    }
+
+    fn value(&mut self, name: &str, content: Content, path: &str, text: &str) {
+        let trimmed = text.trim();
+        let valid = match content {
+            Content::Boolean => matches!(trimmed, "true" | "false" | "1" | "0"),
+            Content::UnsignedShort => trimmed.parse::<u16>().is_ok(),
+            Content::UnsignedLong => trimmed.parse::<u64>().is_ok(),
+            Content::Enumeration(values) => {
+                if !values
+                    .iter()
+                    .any(|value| value.eq_ignore_ascii_case(trimmed))
+                {
+                    self.push(
+                        Severity::Warning,
+                        DiagnosticCode::UnknownValue,
+                        path.to_string(),
+                        format!(
+                            "unknown {name} `{text}`, expected one of {}",
+                            values.join(", ")
+                        ),
+                    );
+                }
+                true
+            }
+            _ => true,
+        };
+
+        if !valid {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::InvalidValue,
+                path.to_string(),
+                format!("`{text}` is not {}", content.describe()),
+            );
+        }
+    }
+
+    fn skip(&mut self, start: &BytesStart<'_>, empty: bool) -> Result<(), quick_xml::Error> {
+        if !empty {
+            self.reader.read_to_end(start.name())?;
+        }
+        Ok(())
+    }
+}
+
+fn element_name(start: &BytesStart<'_>) -> String {
+    String::from_utf8_lossy(start.local_name().as_ref()).into_owned()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    const SAMPLES: &[&str] = &[
+        "tests/data/filelist_mode.xml",
+        "tests/data/mixed_mode.xml",
+        "tests/data/multi_package_bulkfolders.xml",
+        "tests/data/single_package_bulkfolder.xml",
+        "tests/data/minimal_encrypted.xml",
+        "tests/data/legacy_v3.xml",
+        "examples/encrypted.sfdl",
+        "examples/decrypted.sfdl",
+    ];
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(DiagnosticCode, &str)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.path.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_samples_are_valid() {
+        for path in SAMPLES {
+            let diagnostics = SfdlFile::validate_schema(std::fs::read(path).unwrap());
+            assert_eq!(diagnostics, [], "{path}");
+        }
+    }
+
+    #[test]
+    fn test_reports_every_violation() {
+        let xml = std::fs::read_to_string("tests/data/filelist_mode.xml")
+            .unwrap()
+            .replace("<Uploader>File list uploader</Uploader>", "")
+            .replace("<Encrypted>", "<Generator>tool</Generator><Encrypted>")
+            .replace("<Port>21</Port>", "<Port>ftp</Port><Port>21</Port>")
+            .replace("<DataType>Binary</DataType>", "<DataType>EBCDIC</DataType>")
+            .replace("<FileSize>1024</FileSize>", "<FileSize>-1</FileSize>")
+            .replace(
+                "<FileHash>abc123def456</FileHash>",
+                "<FileHash><b/></FileHash>",
+            );
+
+        let diagnostics = SfdlFile::validate_schema(&xml);
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                (DiagnosticCode::UnexpectedElement, "/SFDLFile/Generator"),
+                (
+                    DiagnosticCode::InvalidValue,
+                    "/SFDLFile/ConnectionInfo/Port"
+                ),
+                (
+                    DiagnosticCode::DuplicateElement,
+                    "/SFDLFile/ConnectionInfo/Port"
+                ),
+                (
+                    DiagnosticCode::UnknownValue,
+                    "/SFDLFile/ConnectionInfo/DataType"
+                ),
+                (
+                    DiagnosticCode::InvalidValue,
+                    "/SFDLFile/Packages/SFDLPackage[1]/FileList/FileInfo[2]/FileSize"
+                ),
+                (
+                    DiagnosticCode::UnexpectedElement,
+                    "/SFDLFile/Packages/SFDLPackage[1]/FileList/FileInfo[2]/FileHash/b"
+                ),
+                (DiagnosticCode::MissingElement, "/SFDLFile/Uploader"),
+            ]
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[3].severity, Severity::Warning);
+        assert!(diagnostics[3].message.contains("Binary, ASCII"));
+    }
+
+    #[test]
+    fn test_required_elements_depend_on_version() {
+        let legacy = std::fs::read_to_string("tests/data/legacy_v3.xml").unwrap();
+        assert!(!legacy.contains("DataStaleDetection"));
+        assert_eq!(SfdlFile::validate_schema(&legacy), []);
+
+        let current = std::fs::read_to_string("tests/data/filelist_mode.xml")
+            .unwrap()
+            .replace("<DataStaleDetection>true</DataStaleDetection>", "");
+        assert_eq!(
+            codes(&SfdlFile::validate_schema(&current)),
+            [(
+                DiagnosticCode::MissingElement,
+                "/SFDLFile/ConnectionInfo/DataStaleDetection"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_structural_errors() {
+        let xml = std::fs::read_to_string("tests/data/single_package_bulkfolder.xml").unwrap();
+        let start = xml.find("<SFDLPackage>").unwrap();
+        let end = xml.find("</SFDLPackage>").unwrap() + "</SFDLPackage>".len();
+        let without_packages = format!("{}{}", &xml[..start], &xml[end..]);
+        assert_eq!(
+            codes(&SfdlFile::validate_schema(&without_packages)),
+            [(
+                DiagnosticCode::MissingElement,
+                "/SFDLFile/Packages/SFDLPackage"
+            )]
+        );
+
+        assert_eq!(
+            codes(&SfdlFile::validate_schema("<Other/>")),
+            [(DiagnosticCode::UnexpectedElement, "/Other")]
+        );
+        assert_eq!(
+            codes(&SfdlFile::validate_schema("name = \"sfdl\" <a@b.c>")),
+            [(DiagnosticCode::MalformedXml, "/")]
+        );
+        assert_eq!(
+            codes(&SfdlFile::validate_schema("")),
+            [(DiagnosticCode::MissingElement, "/SFDLFile")]
+        );
+        assert_eq!(
+            codes(&SfdlFile::validate_schema(
+                "<SFDLFile><Description></Uploader>"
+            )),
+            [(DiagnosticCode::MalformedXml, "/")]
+        );
+
+        let truncated = &xml[..xml.rfind("</SFDLFile>").unwrap()];
+        let diagnostics = SfdlFile::validate_schema(truncated);
+        assert_eq!(codes(&diagnostics), [(DiagnosticCode::MalformedXml, "/")]);
+        assert_eq!(
+            diagnostics[0].message,
+            "unexpected end of document, `</SFDLFile>` is missing"
+        );
+        let truncated = &xml[..xml.find("</Description>").unwrap()];
+        assert_eq!(
+            SfdlFile::validate_schema(truncated)[0].message,
+            "unexpected end of document, `</Description>` is missing"
+        );
+        assert!(truncated.parse::<SfdlFile>().is_err());
+    }
+
+    /// A container in which every element of the model occurs.
+    fn populated() -> SfdlFile {
+        let sfdl = SfdlFile::from_file("tests/data/mixed_mode.xml").unwrap();
+        assert!(sfdl.packages.iter().any(|package| {
+            !package.bulk_folder_list.bulk_folder.is_empty()
+                && package
+                    .file_list
+                    .as_ref()
+                    .is_some_and(|list| !list.file_info.is_empty())
+        }));
+        sfdl
+    }
+
+    /// The element paths of the schema, with the XML or the JSON names.
+    fn schema_paths(element: &Element, path: &str, xml: bool, out: &mut BTreeSet<String>) {
+        let name = if xml { element.name } else { element.field };
+        let path = format!("{path}/{name}");
+        match element.content {
+            Content::Record { children, .. } => {
+                for child in children {
+                    schema_paths(child, &path, xml, out);
+                }
+            }
+            // JSON has no wrapper element around the items of a list.
+            Content::List { item, .. } if !xml => {
+                if let Content::Record { children, .. } = item.content {
+                    for child in children {
+                        schema_paths(child, &path, xml, out);
+                    }
+                }
+            }
+            Content::List { item, .. } => schema_paths(item, &path, xml, out),
+            _ => {}
+        }
+        out.insert(path);
+    }
+
+    #[test]
+    fn test_schema_matches_model() {
+        let sfdl = populated();
+        let xml = sfdl.to_xml_string().unwrap();
+        assert_eq!(SfdlFile::validate_schema(&xml), []);
+
+        let mut serialized = BTreeSet::new();
+        let mut reader = Reader::from_str(&xml);
+        let mut path = String::new();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(start) => {
+                    path = format!("{path}/{}", element_name(&start));
+                    serialized.insert(path.clone());
+                }
+                Event::Empty(start) => {
+                    serialized.insert(format!("{path}/{}", element_name(&start)));
+                }
+                Event::End(_) => path.truncate(path.rfind('/').unwrap()),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        let mut expected = BTreeSet::new();
+        schema_paths(&SFDL_FILE, "", true, &mut expected);
+        assert_eq!(serialized, expected);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_schema_matches_model() {
+        fn keys(value: &serde_json::Value, path: &str, out: &mut BTreeSet<String>) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    for (key, value) in map {
+                        let path = format!("{path}/{key}");
+                        keys(value, &path, out);
+                        out.insert(path);
+                    }
+                }
+                serde_json::Value::Array(items) => {
+                    for item in items {
+                        keys(item, path, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let json = populated().to_json().unwrap();
+        let mut serialized = BTreeSet::new();
+        keys(&serde_json::from_str(&json).unwrap(), "", &mut serialized);
+
+        let mut expected = BTreeSet::new();
+        for child in SFDL_FILE_CHILDREN {
+            schema_paths(child, "", false, &mut expected);
+        }
+        assert_eq!(serialized, expected);
+    }
+
+    #[test]
+    fn test_published_schemas_are_current() {
+        assert_eq!(std::fs::read_to_string("schema/sfdl.xsd").unwrap(), xsd());
+        assert_eq!(
+            std::fs::read_to_string("schema/sfdl.schema.json").unwrap(),
+            json_schema()
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_schema_matches_to_json() {
+        fn check(
+            value: &serde_json::Value,
+            schema: &serde_json::Value,
+            root: &serde_json::Value,
+            path: &str,
+        ) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    for (key, value) in map {
+                        let property = &schema["properties"][key];
+                        let property = match property["$ref"].as_str() {
+                            Some(_) => &root["$defs"]["extensions"],
+                            None => property,
+                        };
+                        assert!(!property.is_null(), "{path}.{key}");
+                        check(value, property, root, &format!("{path}.{key}"));
+                    }
+                }
+                serde_json::Value::Array(items) => {
+                    for item in items {
+                        check(item, &schema["items"], root, path);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let schema: serde_json::Value = serde_json::from_str(&json_schema()).unwrap();
+        let mut sfdl = SfdlFile::from_file("tests/data/mixed_mode.xml").unwrap();
+        sfdl.xmlns_xsd = "urn:other".to_string();
+        sfdl.connection_info
+            .extensions
+            .elements
+            .push(crate::ExtensionElement::new(Some("Host"), "<Proxy/>"));
+        let value: serde_json::Value = serde_json::from_str(&sfdl.to_json().unwrap()).unwrap();
+        check(&value, &schema, &schema, "");
+
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(
+            schema["properties"]["packages"]["items"]["additionalProperties"],
+            false
+        );
+
+        let required = schema["properties"]["connection_info"]["required"]
+            .as_array()
+            .unwrap();
+        assert_eq!(required.len(), CONNECTION_INFO_CHILDREN.len());
+    }
+}
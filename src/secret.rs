@@ -0,0 +1,170 @@
+//! Credentials that are wiped from memory once they are no longer needed.
+//!
+//! [`ConnectionInfo::username`] and [`ConnectionInfo::password`] are stored as
+//! [`SecretString`]. With the `zeroize` feature enabled, their buffers are
+//! overwritten with zeros when they are dropped or replaced, and so are the
+//! derived keys and the intermediate plaintext buffers of the
+//! [`crypto`](crate::crypto) module. Without the feature they behave like
+//! plain strings. Either way their [`Debug`](std::fmt::Debug) output is
+//! masked.
+//!
+//! Wiping only covers memory owned by this crate. Passwords passed in as
+//! `&str` remain the caller's responsibility.
+//!
+//! [`ConnectionInfo::username`]: crate::ConnectionInfo::username
+//! [`ConnectionInfo::password`]: crate::ConnectionInfo::password
+
+use std::fmt;
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+/// Placeholder written instead of a masked value.
+pub(crate) const REDACTED: &str = "[redacted]";
+
+/// A string holding a credential.
+///
+/// It dereferences to `str` and serializes like a plain string. Its
+/// [`Debug`](fmt::Debug) output is masked, so credentials do not end up in
+/// logs; use [`SecretString::as_str`] to get at the value.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Wraps `value`.
+    #[must_use]
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// Returns the credential.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Replaces the credential, wiping the previous one.
+    pub fn set(&mut self, value: impl Into<String>) {
+        replace(&mut self.0, value.into());
+    }
+
+    /// The underlying buffer, for replacing the value in place.
+    pub(crate) fn as_mut_string(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for SecretString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl PartialEq<str> for SecretString {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for SecretString {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+/// Overwrites `value` with zeros if the `zeroize` feature is enabled.
+#[cfg(feature = "zeroize")]
+pub(crate) fn wipe<T: zeroize::Zeroize + ?Sized>(value: &mut T) {
+    value.zeroize();
+}
+
+/// Overwrites `value` with zeros if the `zeroize` feature is enabled.
+#[cfg(not(feature = "zeroize"))]
+pub(crate) fn wipe<T: ?Sized>(_value: &mut T) {}
+
+/// Wipes `field` and then stores `value` in it.
+pub(crate) fn replace(field: &mut String, value: String) {
+    wipe(field);
+    *field = value;
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::SfdlFile;
+
+    #[test]
+    fn test_serializes_as_plain_string() {
+        let xml = std::fs::read_to_string("tests/data/mixed_mode.xml").unwrap();
+        let mut sfdl: SfdlFile = xml.parse().unwrap();
+        sfdl.connection_info.password.set("p<a>ss");
+
+        let reparsed: SfdlFile = sfdl.to_xml_string().unwrap().parse().unwrap();
+        assert_eq!(reparsed.connection_info.password, "p<a>ss");
+        assert_eq!(reparsed, sfdl);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut field = "old".to_string();
+        replace(&mut field, "new".to_string());
+        assert_eq!(field, "new");
+
+        let mut secret = SecretString::new("old");
+        secret.set("new");
+        assert_eq!(secret.as_str(), "new");
+        assert_eq!(secret.len(), 3);
+    }
+
+    #[test]
+    fn test_debug_is_masked() {
+        let secret = SecretString::new("hunter2");
+        assert_eq!(format!("{secret:?}"), "[redacted]");
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_wipe_clears() {
+        let mut bytes = vec![1u8, 2, 3];
+        wipe(&mut bytes);
+        assert!(bytes.is_empty());
+
+        let mut key = [7u8; 16];
+        wipe(&mut key);
+        assert_eq!(key, [0; 16]);
+    }
+}
@@ -59,27 +59,36 @@
 //! sfdl.decrypt("S3cr3tP4ssw0rd!").unwrap();
 //! ```
 
-use std::fmt::Write as FmtWrite;
+use std::convert::Infallible;
+use std::fmt::{self, Write as FmtWrite};
 use std::fs;
-use std::io::BufRead;
+use std::io::{self, BufRead};
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::crypto;
-use crate::error::{ParseError, SfdlError};
+use crate::crypto::{CandidateMatch, DecryptReport, EncryptionProfile, EncryptionState, SfdlKey};
+use crate::encoding::SourceEncoding;
+use crate::error::{DecryptError, ParseError, SfdlError};
+use crate::extensions::{self, Extensions};
+use crate::iv::{IvSource, OsIvSource};
+use crate::secret::{SecretString, REDACTED};
+use crate::version::LegacyElements;
+use crate::writer::WriteOptions;
+use crate::{crypto, encoding, version};
 
 const XMLNS_XSD: &str = "http://www.w3.org/2001/XMLSchema";
 const XMLNS_XSI: &str = "http://www.w3.org/2001/XMLSchema-instance";
 
 #[allow(clippy::must_use_candidate)]
-fn default_xmlns_xsd() -> String {
+pub(crate) fn default_xmlns_xsd() -> String {
     XMLNS_XSD.to_string()
 }
 
 #[allow(clippy::must_use_candidate)]
-fn default_xmlns_xsi() -> String {
+pub(crate) fn default_xmlns_xsi() -> String {
     XMLNS_XSI.to_string()
 }
 
@@ -98,6 +107,8 @@ pub struct SfdlFile {
     /// Uploader of the SFDL container.
     pub uploader: String,
     /// Version of the SFDL file format.
+    ///
+    /// Determines the XML layout, see the [`crate::version`] module.
     #[serde(rename = "SFDLFileVersion")]
     pub sfdlfile_version: u16,
     /// Whether the encryptable fields are currently encrypted.
@@ -108,9 +119,14 @@ pub struct SfdlFile {
     pub packages: Packages,
     /// Maximum number of concurrent download threads.
     pub max_download_threads: u16,
+    /// Unknown attributes and child elements of `SFDLFile`.
+    #[serde(skip)]
+    pub extensions: Extensions,
 }
 
 /// Connection settings for the server described by the SFDL container.
+///
+/// The [`Debug`](fmt::Debug) output masks the username and password.
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
@@ -120,9 +136,9 @@ pub struct ConnectionInfo {
     /// Server port.
     pub port: u16,
     /// Username for authentication.
-    pub username: String,
+    pub username: SecretString,
     /// Password for authentication.
-    pub password: String,
+    pub password: SecretString,
     /// Whether authentication is required.
     pub auth_required: bool,
     /// FTP data connection mode.
@@ -134,10 +150,11 @@ pub struct ConnectionInfo {
     /// Encryption mode for the connection.
     pub encryption_mode: EncryptionMode,
     /// Method used for listing directory contents.
-    pub list_method: String,
+    pub list_method: ListMethod,
     /// Default path on the server.
     ///
-    /// Note: the reference SFDL implementation leaves this field plaintext.
+    /// Whether this field is encrypted depends on the [`EncryptionProfile`]:
+    /// SFDL.NET encrypts it, `n0ix/SFDL.Container` leaves it plaintext.
     pub default_path: String,
     /// Whether to force a single connection.
     pub force_single_connection: bool,
@@ -145,6 +162,13 @@ pub struct ConnectionInfo {
     pub data_stale_detection: bool,
     /// Whether special server compatibility mode is enabled.
     pub special_server_mode: bool,
+    /// The elements above that a legacy container carried although its
+    /// layout does not define them, see [`crate::version`].
+    #[serde(skip)]
+    pub legacy_elements: LegacyElements,
+    /// Unknown attributes and child elements of `ConnectionInfo`.
+    #[serde(skip)]
+    pub extensions: Extensions,
 }
 
 /// A list of [`SfdlPackage`] entries.
@@ -187,6 +211,9 @@ pub struct SfdlPackage {
     /// Optional list of individual files.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_list: Option<FileList>,
+    /// Unknown attributes and child elements of `SFDLPackage`.
+    #[serde(skip)]
+    pub extensions: Extensions,
 }
 
 /// A list of [`BulkFolder`] entries.
@@ -239,72 +266,150 @@ pub struct FileInfo {
     pub package_name: String,
 }
 
-/// FTP data connection mode.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-pub enum DataConnectionType {
-    /// Automatically choose passive mode.
-    #[serde(rename = "AutoPassive")]
-    AutoPassive,
-    /// Automatically choose active mode.
-    #[serde(rename = "AutoActive")]
-    AutoActive,
-    /// Use the EPRT command.
-    #[serde(rename = "EPRT")]
-    EPRT,
-    /// Use the EPSV command.
-    #[serde(rename = "EPSV")]
-    EPSV,
-    /// Use passive mode.
-    #[serde(rename = "PASV")]
-    PASV,
-    /// Use extended passive mode.
-    #[serde(rename = "PASVEX")]
-    PASVEX,
-    /// Use the PORT command.
-    #[serde(rename = "PORT")]
-    PORT,
+/// Defines an enum that parses known values case-insensitively and keeps
+/// unknown values verbatim in an `Other` variant.
+///
+/// Known values are always written in their canonical spelling, `Other` values
+/// are written exactly as they were read.
+macro_rules! lenient_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident => $value:literal,
+            )+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )+
+            /// A value unknown to this crate, kept verbatim.
+            Other(String),
+        }
+
+        impl $name {
+            /// The known values, as they are written in the XML.
+            pub(crate) const VALUES: &'static [&'static str] = &[$($value,)+];
+
+            /// Returns the value as it is written in the XML.
+            #[must_use]
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $value,)+
+                    Self::Other(value) => value,
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Infallible;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let trimmed = s.trim();
+                $(
+                    if trimmed.eq_ignore_ascii_case($value) {
+                        return Ok(Self::$variant);
+                    }
+                )+
+                Ok(Self::Other(s.to_string()))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                let Ok(parsed) = value.parse();
+                Ok(parsed)
+            }
+        }
+    };
 }
 
-/// Transfer data type.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-pub enum DataType {
-    /// Binary transfer mode.
-    #[serde(rename = "Binary")]
-    Binary,
-    /// ASCII transfer mode.
-    #[serde(rename = "ASCII")]
-    ASCII,
+lenient_enum! {
+    /// FTP data connection mode.
+    pub enum DataConnectionType {
+        /// Automatically choose passive mode.
+        AutoPassive => "AutoPassive",
+        /// Automatically choose active mode.
+        AutoActive => "AutoActive",
+        /// Use the EPRT command.
+        EPRT => "EPRT",
+        /// Use the EPSV command.
+        EPSV => "EPSV",
+        /// Use passive mode.
+        PASV => "PASV",
+        /// Use extended passive mode.
+        PASVEX => "PASVEX",
+        /// Use the PORT command.
+        PORT => "PORT",
+    }
 }
 
-/// Character encoding used by the target server.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-pub enum CharacterEncoding {
-    /// Standard encoding.
-    #[serde(rename = "Standard")]
-    Standard,
-    /// UTF-8 encoding.
-    #[serde(rename = "UTF8")]
-    UTF8,
-    /// UTF-7 encoding.
-    #[serde(rename = "UTF7")]
-    UTF7,
-    /// ASCII encoding.
-    #[serde(rename = "ASCII")]
-    ASCII,
+lenient_enum! {
+    /// Transfer data type.
+    pub enum DataType {
+        /// Binary transfer mode.
+        Binary => "Binary",
+        /// ASCII transfer mode.
+        ASCII => "ASCII",
+    }
 }
 
-/// Connection encryption mode.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-pub enum EncryptionMode {
-    /// No encryption.
-    #[serde(rename = "None")]
-    None,
-    /// SSL encryption.
-    #[serde(rename = "SSL")]
-    SSL,
-    /// TLS encryption.
-    #[serde(rename = "TLS")]
-    TLS,
+lenient_enum! {
+    /// Character encoding used by the target server.
+    pub enum CharacterEncoding {
+        /// Standard encoding.
+        Standard => "Standard",
+        /// UTF-8 encoding.
+        UTF8 => "UTF8",
+        /// UTF-7 encoding.
+        UTF7 => "UTF7",
+        /// ASCII encoding.
+        ASCII => "ASCII",
+    }
+}
+
+lenient_enum! {
+    /// Connection encryption mode.
+    pub enum EncryptionMode {
+        /// No encryption.
+        None => "None",
+        /// SSL encryption.
+        SSL => "SSL",
+        /// TLS encryption.
+        TLS => "TLS",
+    }
+}
+
+lenient_enum! {
+    /// Method used for listing directory contents.
+    pub enum ListMethod {
+        /// Always use the `LIST` command.
+        ForceList => "ForceList",
+        /// Always use the `NLST` command.
+        ForceNameList => "ForceNameList",
+        /// Use the `NLST` command if the server supports it.
+        NameList => "NameList",
+        /// Use an `ls` style listing.
+        UseLS => "UseLS",
+    }
 }
 
 impl SfdlFile {
@@ -325,17 +430,48 @@ impl SfdlFile {
     ///
     /// let sfdl = SfdlFile::from_reader(reader).unwrap();
     /// ```
-    pub fn from_reader<R>(reader: R) -> Result<Self, ParseError>
+    pub fn from_reader<R>(mut reader: R) -> Result<Self, ParseError>
     where
         R: BufRead,
     {
-        quick_xml::de::from_reader(reader).map_err(ParseError::InvalidSfdlDeserialize)
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).map_err(|err| {
+            ParseError::InvalidSfdlDeserialize(quick_xml::Error::Io(Arc::new(err)).into())
+        })?;
+        Self::from_bytes(&content).map(|(sfdl, _)| sfdl)
+    }
+
+    /// Parse a SFDL file from raw bytes, detecting their text encoding.
+    ///
+    /// The encoding is taken from a byte order mark, otherwise from the XML
+    /// declaration. Input without either that is not valid UTF-8 is read as
+    /// Windows-1252. The detected encoding is returned alongside the file so
+    /// it can be reused with [`WriteOptions::source_encoding`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseError::UnsupportedEncoding`] if the declared encoding is
+    /// unknown, [`ParseError::MalformedInput`] if the bytes are not valid in
+    /// the detected encoding, or [`ParseError::InvalidSfdlDeserialize`] if the
+    /// XML cannot be parsed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// let bytes = std::fs::read("examples/encrypted.sfdl").unwrap();
+    ///
+    /// let (sfdl, source) = SfdlFile::from_bytes(&bytes).unwrap();
+    /// assert_eq!(source.encoding, sfdl::encoding_rs::UTF_8);
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, SourceEncoding), ParseError> {
+        let (content, source) = encoding::decode(bytes)?;
+        Ok((content.parse()?, source))
     }
 
-    /// Read a file from a string.
+    /// Read a SFDL file from a path.
     ///
-    /// This is a convenience function for using [`fs::read_to_string`]
-    /// with fewer imports.
+    /// The text encoding is detected as in [`SfdlFile::from_bytes`].
     ///
     /// # Errors
     ///
@@ -348,28 +484,91 @@ impl SfdlFile {
     /// SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
     /// ```
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SfdlError> {
-        let content = fs::read_to_string(path)?;
-        let sfdl: Self = content.parse()?;
+        let content = fs::read(path)?;
+        let (sfdl, _) = Self::from_bytes(&content)?;
 
         Ok(sfdl)
     }
 
     /// Encrypt all encryptable fields in the SFDL file.
     ///
-    /// This is an atomic operation: if any field fails to encrypt, the file is
-    /// left unchanged. On success the [`SfdlFile::encrypted`] flag is set to
-    /// `true`.
+    /// On success the [`SfdlFile::encrypted`] flag is set to `true`.
     ///
     /// # Errors
     ///
-    /// Returns [`SfdlError::AlreadyEncrypted`] if the file is already encrypted,
-    /// or an encryption error if a field cannot be encrypted.
+    /// Returns [`SfdlError::AlreadyEncrypted`] if the file is already encrypted.
     pub fn encrypt(&mut self, password: &str) -> Result<(), SfdlError> {
+        self.encrypt_with_key(&SfdlKey::from_password(password))
+    }
+
+    /// Encrypt all encryptable fields, taking the IV of each field from
+    /// `ivs`.
+    ///
+    /// [`SfdlFile::encrypt`] uses secure random IVs. With a deterministic
+    /// source such as [`SeededIvSource`](crate::SeededIvSource) the output is
+    /// reproducible, e.g. for golden test fixtures.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::AlreadyEncrypted`] if the file is already encrypted.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::{FixedIvSource, SfdlFile};
+    /// let mut sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+    /// sfdl.encrypt_with("S3cr3tP4ssw0rd!", FixedIvSource::new([[7; 16]]))
+    ///     .unwrap();
+    /// ```
+    pub fn encrypt_with(&mut self, password: &str, ivs: impl IvSource) -> Result<(), SfdlError> {
         if self.encrypted {
             return Err(SfdlError::AlreadyEncrypted);
         }
 
-        *self = crypto::encrypt_sfdl(self, password);
+        let key = SfdlKey::from_password(password);
+        *self = crypto::encrypt_sfdl(self, &key, EncryptionProfile::default(), ivs);
+        self.encrypted = true;
+        Ok(())
+    }
+
+    /// Encrypt all encryptable fields with an already derived key.
+    ///
+    /// Behaves like [`SfdlFile::encrypt`], but skips the key derivation, so a
+    /// cached [`SfdlKey`] can be reused across containers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::AlreadyEncrypted`] if the file is already encrypted.
+    pub fn encrypt_with_key(&mut self, key: &SfdlKey) -> Result<(), SfdlError> {
+        if self.encrypted {
+            return Err(SfdlError::AlreadyEncrypted);
+        }
+
+        *self = crypto::encrypt_sfdl(self, key, EncryptionProfile::default(), OsIvSource);
+        self.encrypted = true;
+        Ok(())
+    }
+
+    /// Encrypt the fields selected by `profile`.
+    ///
+    /// [`SfdlFile::encrypt`] uses [`EncryptionProfile::default`], which
+    /// encrypts every field like SFDL.NET does. Use
+    /// [`EncryptionProfile::sfdl_container`] to leave `DefaultPath` plaintext.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::AlreadyEncrypted`] if the file is already encrypted.
+    pub fn encrypt_with_profile(
+        &mut self,
+        password: &str,
+        profile: EncryptionProfile,
+    ) -> Result<(), SfdlError> {
+        if self.encrypted {
+            return Err(SfdlError::AlreadyEncrypted);
+        }
+
+        let key = SfdlKey::from_password(password);
+        *self = crypto::encrypt_sfdl(self, &key, profile, OsIvSource);
         self.encrypted = true;
         Ok(())
     }
@@ -378,29 +577,331 @@ impl SfdlFile {
     ///
     /// This is an atomic operation: if any field fails to decrypt, the file is
     /// left unchanged. On success the [`SfdlFile::encrypted`] flag is set to
-    /// `false`.
+    /// `false`. `DefaultPath` is only decrypted if it is ciphertext under
+    /// `password`, see [`EncryptionProfile`].
     ///
     /// # Errors
     ///
     /// Returns [`SfdlError::NotEncrypted`] if the file is not encrypted,
     /// or a decryption error if the password is wrong or the ciphertext is invalid.
+    /// The decryption error names the failing field, see [`DecryptError::path`].
     pub fn decrypt(&mut self, password: &str) -> Result<(), SfdlError> {
+        self.decrypt_with_key(&SfdlKey::from_password(password))
+    }
+
+    /// Decrypt all decryptable fields with an already derived key.
+    ///
+    /// Behaves like [`SfdlFile::decrypt`], but skips the key derivation, so a
+    /// cached [`SfdlKey`] can be reused across containers.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SfdlFile::decrypt`].
+    pub fn decrypt_with_key(&mut self, key: &SfdlKey) -> Result<(), SfdlError> {
         if !self.encrypted {
             return Err(SfdlError::NotEncrypted);
         }
 
-        *self = crypto::decrypt_sfdl(self, password)?;
+        *self = crypto::decrypt_sfdl(self, key)?;
+        self.encrypted = false;
+        Ok(())
+    }
+
+    /// Re-encrypt all encrypted fields from `old_password` to `new_password`.
+    ///
+    /// Every field goes straight from the old ciphertext to the new one, so the
+    /// decrypted container is never stored in `self`. This is an atomic
+    /// operation: if any field fails to decrypt, the file is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::NotEncrypted`] if the file is not encrypted, or a
+    /// decryption error if `old_password` is wrong or the ciphertext is
+    /// invalid.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// let mut sfdl = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+    /// sfdl.rekey("S3cr3tP4ssw0rd!", "n3w-p4ssw0rd").unwrap();
+    ///
+    /// assert!(sfdl.verify_password("n3w-p4ssw0rd"));
+    /// sfdl.decrypt("n3w-p4ssw0rd").unwrap();
+    /// assert_eq!(sfdl.description, "MyDescription");
+    /// ```
+    pub fn rekey(&mut self, old_password: &str, new_password: &str) -> Result<(), SfdlError> {
+        self.rekey_with_key(
+            &SfdlKey::from_password(old_password),
+            &SfdlKey::from_password(new_password),
+        )
+    }
+
+    /// Re-encrypt all encrypted fields with already derived keys.
+    ///
+    /// Behaves like [`SfdlFile::rekey`], but skips the key derivation.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SfdlFile::rekey`].
+    pub fn rekey_with_key(&mut self, old: &SfdlKey, new: &SfdlKey) -> Result<(), SfdlError> {
+        if !self.encrypted {
+            return Err(SfdlError::NotEncrypted);
+        }
+
+        *self = crypto::rekey_sfdl(self, old, new, OsIvSource)?;
+        Ok(())
+    }
+
+    /// Classify the file as plaintext, encrypted or mixed by inspecting its
+    /// encryptable fields, ignoring the [`SfdlFile::encrypted`] flag.
+    ///
+    /// A field counts as encrypted if it is valid base64 of an IV followed by
+    /// at least one AES block. Empty fields are not counted, and a file
+    /// without any non-empty field is reported as plaintext. `DefaultPath` is
+    /// ignored because not every [`EncryptionProfile`] encrypts it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::{EncryptionState, SfdlFile};
+    /// let mut sfdl = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+    /// sfdl.encrypted = false;
+    ///
+    /// assert_eq!(sfdl.detect_encryption(), EncryptionState::Encrypted);
+    /// ```
+    #[must_use]
+    pub fn detect_encryption(&self) -> EncryptionState {
+        crypto::detect_state(self)
+    }
+
+    /// Decrypt every field that looks encrypted, trusting
+    /// [`SfdlFile::detect_encryption`] instead of the [`SfdlFile::encrypted`]
+    /// flag.
+    ///
+    /// Fields that do not look encrypted are kept, so a mixed file ends up
+    /// fully decrypted. Like [`SfdlFile::decrypt`] this is atomic, and on
+    /// success the flag is set to `false`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::NotEncrypted`] if no field looks encrypted, or a
+    /// decryption error if the password is wrong or a field is malformed.
+    pub fn decrypt_detected(&mut self, password: &str) -> Result<(), SfdlError> {
+        if self.detect_encryption() == EncryptionState::Plaintext {
+            return Err(SfdlError::NotEncrypted);
+        }
+
+        *self = crypto::decrypt_detected(self, &SfdlKey::from_password(password))?;
         self.encrypted = false;
         Ok(())
     }
 
+    /// Decrypt every field that holds valid ciphertext for `password` and keep
+    /// all others unchanged, reporting which is which.
+    ///
+    /// This reads containers from generators that encrypt only some fields,
+    /// for example the host and password but not the package names. Unlike
+    /// [`SfdlFile::decrypt_detected`], a field that merely looks like
+    /// ciphertext but does not decrypt is passed through instead of failing.
+    /// The [`SfdlFile::encrypted`] flag is ignored, and set to `false` on
+    /// success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DecryptError::InvalidPassword`] if some field looks encrypted
+    /// but none decrypts, which almost always means the password is wrong. The
+    /// file is left unchanged in that case.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// let mut sfdl = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+    /// sfdl.packages[0].package_name = "plain".to_string();
+    ///
+    /// let report = sfdl.decrypt_lenient("S3cr3tP4ssw0rd!").unwrap();
+    /// assert_eq!(report.passed_through, ["packages[0].package_name"]);
+    /// assert!(report.decrypted.iter().any(|field| field == "description"));
+    /// assert_eq!(sfdl.description, "MyDescription");
+    /// ```
+    pub fn decrypt_lenient(&mut self, password: &str) -> Result<DecryptReport, SfdlError> {
+        let (sfdl, report) = crypto::decrypt_lenient(self, &SfdlKey::from_password(password))?;
+        *self = sfdl;
+        self.encrypted = false;
+        Ok(report)
+    }
+
+    /// Encrypt every field that does not look encrypted yet, trusting
+    /// [`SfdlFile::detect_encryption`] instead of the [`SfdlFile::encrypted`]
+    /// flag.
+    ///
+    /// A mixed file ends up fully encrypted. On success the flag is set to
+    /// `true`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::AlreadyEncrypted`] if every non-empty field
+    /// already looks encrypted.
+    pub fn encrypt_detected(&mut self, password: &str) -> Result<(), SfdlError> {
+        if self.detect_encryption() == EncryptionState::Encrypted {
+            return Err(SfdlError::AlreadyEncrypted);
+        }
+
+        *self = crypto::encrypt_detected(self, &SfdlKey::from_password(password), OsIvSource);
+        self.encrypted = true;
+        Ok(())
+    }
+
+    /// Check whether `password` decrypts this SFDL file.
+    ///
+    /// This is a cheap check that decrypts only the last block of a single
+    /// field, see [`SfdlFile::check_password`]. It returns `false` for files
+    /// that are not encrypted.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// let sfdl = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+    ///
+    /// assert!(sfdl.verify_password("S3cr3tP4ssw0rd!"));
+    /// assert!(!sfdl.verify_password("hunter2"));
+    /// ```
+    #[must_use]
+    pub fn verify_password(&self, password: &str) -> bool {
+        self.check_password(password).is_ok()
+    }
+
+    /// Check whether `password` decrypts this SFDL file, reporting why not.
+    ///
+    /// The shortest non-empty top-level field is used, and only its last
+    /// block is decrypted to validate the PKCS#7 padding and UTF-8. A wrong
+    /// password passes with negligible probability, in which case
+    /// [`SfdlFile::decrypt`] still fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::NotEncrypted`] if the file is not encrypted,
+    /// [`DecryptError::InvalidPassword`] or [`DecryptError::InvalidEncoding`]
+    /// if the password is wrong, or another decryption error if the field is
+    /// malformed or all top-level fields are empty.
+    pub fn check_password(&self, password: &str) -> Result<(), SfdlError> {
+        if !self.encrypted {
+            return Err(SfdlError::NotEncrypted);
+        }
+
+        crypto::verify_sfdl(self, &SfdlKey::from_password(password))?;
+        Ok(())
+    }
+
+    /// Decrypt the SFDL file with the first of `candidates` that fits.
+    ///
+    /// Each candidate is first checked against a single short field, so wrong
+    /// passwords are rejected cheaply. The candidates are consumed lazily and
+    /// the search stops at the first match. Like [`SfdlFile::decrypt`] this is
+    /// atomic: the file is only changed once a candidate has decrypted every
+    /// field.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SfdlError::NotEncrypted`] if the file is not encrypted,
+    /// [`DecryptError::InvalidPassword`] if no candidate matches, or another decryption error if the ciphertext
+    /// is malformed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// let mut sfdl = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+    ///
+    /// let found = sfdl
+    ///     .decrypt_with_candidates(["hunter2", "S3cr3tP4ssw0rd!"])
+    ///     .unwrap();
+    /// assert_eq!(found.index, 1);
+    /// assert!(!sfdl.encrypted);
+    /// ```
+    pub fn decrypt_with_candidates<I>(&mut self, candidates: I) -> Result<CandidateMatch, SfdlError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        if !self.encrypted {
+            return Err(SfdlError::NotEncrypted);
+        }
+
+        let found = crypto::find_candidate(self, candidates)?;
+        self.apply_candidate(found)
+    }
+
+    /// Like [`SfdlFile::decrypt_with_candidates`], but tests the candidates on
+    /// all available cores.
+    ///
+    /// The candidates are collected up front. The result is the same as for
+    /// the sequential search, so the candidate with the lowest index wins if
+    /// several match.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SfdlFile::decrypt_with_candidates`].
+    pub fn decrypt_with_candidates_parallel<I>(
+        &mut self,
+        candidates: I,
+    ) -> Result<CandidateMatch, SfdlError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str> + Sync,
+    {
+        if !self.encrypted {
+            return Err(SfdlError::NotEncrypted);
+        }
+
+        let candidates: Vec<I::Item> = candidates.into_iter().collect();
+        let found = crypto::find_candidate_parallel(self, &candidates)?;
+        self.apply_candidate(found)
+    }
+
+    fn apply_candidate(
+        &mut self,
+        found: Option<(CandidateMatch, SfdlFile)>,
+    ) -> Result<CandidateMatch, SfdlError> {
+        let (found, decrypted) = found.ok_or(DecryptError::InvalidPassword)?;
+        *self = decrypted;
+        self.encrypted = false;
+        Ok(found)
+    }
+
     /// Serialize this SFDL file into an XML string.
     ///
+    /// The output is compact: a single line without an XML declaration. Use
+    /// [`SfdlFile::to_xml_string_with`] to control the formatting.
+    ///
     /// # Errors
     ///
     /// Returns [`ParseError::InvalidSfdlSerialize`] if serialization fails.
     pub fn to_xml_string(&self) -> Result<String, ParseError> {
-        quick_xml::se::to_string(self).map_err(ParseError::InvalidSfdlSerialize)
+        self.to_xml_string_with(&WriteOptions::default())
+    }
+
+    /// Serialize this SFDL file into an XML string formatted according to
+    /// `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseError::InvalidSfdlSerialize`] if serialization fails.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::{SfdlFile, WriteOptions};
+    /// # let sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+    /// let xml = sfdl.to_xml_string_with(&WriteOptions::dotnet()).unwrap();
+    /// assert!(xml.starts_with("\u{FEFF}<?xml version=\"1.0\" encoding=\"utf-8\"?>\r\n"));
+    /// ```
+    pub fn to_xml_string_with(&self, options: &WriteOptions) -> Result<String, ParseError> {
+        let mut content = String::new();
+        self.to_xml_writer_with(&mut content, options)?;
+        Ok(content)
     }
 
     /// Serialize this SFDL file as XML into the provided writer.
@@ -411,12 +912,98 @@ impl SfdlFile {
     ///
     /// Returns [`ParseError::InvalidSfdlSerialize`] if serialization fails.
     pub fn to_xml_writer<W: FmtWrite>(&self, writer: W) -> Result<(), ParseError> {
-        quick_xml::se::to_writer(writer, self).map_err(ParseError::InvalidSfdlSerialize)?;
+        self.to_xml_writer_with(writer, &WriteOptions::default())
+    }
+
+    /// Serialize this SFDL file as XML formatted according to `options` into
+    /// the provided writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseError::InvalidSfdlSerialize`] if serialization fails.
+    pub fn to_xml_writer_with<W: FmtWrite>(
+        &self,
+        mut writer: W,
+        options: &WriteOptions,
+    ) -> Result<(), ParseError> {
+        let content = self.to_formatted(options)?;
+        if options.bom {
+            writer
+                .write_char('\u{FEFF}')
+                .map_err(|err| ParseError::InvalidSfdlSerialize(err.into()))?;
+        }
+        writer
+            .write_str(&content)
+            .map_err(|err| ParseError::InvalidSfdlSerialize(err.into()))
+    }
+
+    /// Serialize this SFDL file as XML into an [`io::Write`],
+    /// such as a file, a socket or a compressing encoder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing fails.
+    pub fn to_io_writer<W: io::Write>(&self, writer: W) -> Result<(), SfdlError> {
+        self.to_io_writer_with(writer, &WriteOptions::default())
+    }
+
+    /// Serialize this SFDL file formatted according to `options` into an
+    /// [`io::Write`].
+    ///
+    /// The bytes are the same as those of [`SfdlFile::to_xml_bytes_with`]. The
+    /// writer is flushed afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing fails.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::{SfdlFile, WriteOptions};
+    /// let sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+    ///
+    /// let mut out = Vec::new();
+    /// sfdl.to_io_writer_with(&mut out, &WriteOptions::dotnet()).unwrap();
+    /// assert!(out.starts_with(b"\xEF\xBB\xBF<?xml"));
+    /// ```
+    pub fn to_io_writer_with<W: io::Write>(
+        &self,
+        mut writer: W,
+        options: &WriteOptions,
+    ) -> Result<(), SfdlError> {
+        let content = self.to_xml_bytes_with(options)?;
+        writer.write_all(&content)?;
+        writer.flush()?;
+
         Ok(())
     }
 
+    /// Serialize this SFDL file into bytes in the encoding given by `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseError::InvalidSfdlSerialize`] if serialization fails.
+    pub fn to_xml_bytes_with(&self, options: &WriteOptions) -> Result<Vec<u8>, ParseError> {
+        let content = self.to_formatted(options)?;
+        Ok(encoding::encode(&content, options.encoding, options.bom))
+    }
+
+    fn to_formatted(&self, options: &WriteOptions) -> Result<String, ParseError> {
+        let mut content = String::new();
+        version::to_writer(self, &mut content)?;
+        let content = extensions::inject(self, &content)?;
+
+        let mut formatted = String::with_capacity(content.len());
+        crate::writer::format(&content, options, &mut formatted)?;
+        Ok(formatted)
+    }
+
     /// Serialize and write this SFDL file to a file path.
     ///
+    /// The file is replaced atomically, see the [`writer`](crate::writer)
+    /// module, so a crash never leaves a truncated container behind.
+    ///
     /// # Errors
     ///
     /// Returns an error if serialization or writing to the file fails.
@@ -429,18 +1016,198 @@ impl SfdlFile {
     /// sfdl.write("out.sfdl").unwrap();
     /// ```
     pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), SfdlError> {
-        let content = self.to_xml_string()?;
-        fs::write(path, content)?;
+        self.write_with(path, &WriteOptions::default())
+    }
+
+    /// Serialize and write this SFDL file to a file path, formatted according
+    /// to `options`.
+    ///
+    /// Like [`SfdlFile::write`] the file is replaced atomically. With
+    /// [`WriteOptions::backup`] the previous file is kept as `<name>.bak`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing to the file fails.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use sfdl::{SfdlFile, WriteOptions};
+    /// # let sfdl = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+    /// sfdl.write("out.sfdl").unwrap();
+    /// sfdl.write_with("out.sfdl", &WriteOptions::dotnet().backup(true))
+    ///     .unwrap();
+    /// assert!(std::path::Path::new("out.sfdl.bak").exists());
+    /// # std::fs::remove_file("out.sfdl.bak").unwrap();
+    /// ```
+    pub fn write_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WriteOptions,
+    ) -> Result<(), SfdlError> {
+        let content = self.to_xml_bytes_with(options)?;
+        crate::writer::write_atomic(path.as_ref(), &content, options.backup)?;
 
         Ok(())
     }
+
+    /// Total size in bytes of all files listed in the packages' file lists.
+    ///
+    /// Bulk folders carry no size, so packages without a file list count as
+    /// `0`.
+    #[must_use]
+    pub fn total_size(&self) -> u64 {
+        self.packages.iter().map(SfdlPackage::total_size).sum()
+    }
+
+    /// A view of this file whose [`Debug`](fmt::Debug) output also masks
+    /// [`ConnectionInfo::host`].
+    ///
+    /// The regular `Debug` output already masks the username and password.
+    ///
+    /// ```rust
+    /// # use sfdl::SfdlFile;
+    /// let sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+    /// let debug = format!("{:?}", sfdl.redacted());
+    /// assert!(!debug.contains(&sfdl.connection_info.host));
+    /// ```
+    #[must_use]
+    pub fn redacted(&self) -> Redacted<'_> {
+        Redacted(self)
+    }
+}
+
+/// A short summary for logs and listings, for example
+/// `MyDescription by MyUploader: 2 packages, 1.4 GiB`.
+///
+/// Credentials and the host are never included.
+impl fmt::Display for SfdlFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)?;
+        if !self.uploader.is_empty() {
+            write!(f, " by {}", self.uploader)?;
+        }
+        let packages = self.packages.len();
+        let plural = if packages == 1 { "" } else { "s" };
+        write!(f, ": {packages} package{plural}, ")?;
+        write_size(f, self.total_size())
+    }
+}
+
+/// Writes `bytes` in binary units with one decimal, like `1.4 GiB`.
+fn write_size(f: &mut fmt::Formatter<'_>, bytes: u64) -> fmt::Result {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < 1024 {
+        return write!(f, "{bytes} B");
+    }
+    let mut unit = 0;
+    let mut scale = 1024_u128;
+    while unit + 1 < UNITS.len() && u128::from(bytes) >= scale * 1024 {
+        scale *= 1024;
+        unit += 1;
+    }
+    let tenths = u128::from(bytes) * 10 / scale;
+    write!(f, "{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+}
+
+/// [`Debug`](fmt::Debug) view of an [`SfdlFile`] that masks the host in
+/// addition to the credentials, see [`SfdlFile::redacted`].
+#[derive(Clone, Copy)]
+pub struct Redacted<'a>(&'a SfdlFile);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let SfdlFile {
+            xmlns_xsd,
+            xmlns_xsi,
+            description,
+            uploader,
+            sfdlfile_version,
+            encrypted,
+            connection_info,
+            packages,
+            max_download_threads,
+            extensions,
+        } = self.0;
+        f.debug_struct("SfdlFile")
+            .field("xmlns_xsd", xmlns_xsd)
+            .field("xmlns_xsi", xmlns_xsi)
+            .field("description", description)
+            .field("uploader", uploader)
+            .field("sfdlfile_version", sfdlfile_version)
+            .field("encrypted", encrypted)
+            .field("connection_info", &RedactedConnection(connection_info))
+            .field("packages", packages)
+            .field("max_download_threads", max_download_threads)
+            .field("extensions", extensions)
+            .finish()
+    }
+}
+
+/// [`Debug`](fmt::Debug) view of a [`ConnectionInfo`] with the host masked.
+struct RedactedConnection<'a>(&'a ConnectionInfo);
+
+impl fmt::Debug for RedactedConnection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ConnectionInfo {
+            host: _,
+            port,
+            username,
+            password,
+            auth_required,
+            data_connection_type,
+            data_type,
+            character_encoding,
+            encryption_mode,
+            list_method,
+            default_path,
+            force_single_connection,
+            data_stale_detection,
+            special_server_mode,
+            legacy_elements,
+            extensions,
+        } = self.0;
+        f.debug_struct("ConnectionInfo")
+            .field("host", &format_args!("{REDACTED}"))
+            .field("port", port)
+            .field("username", username)
+            .field("password", password)
+            .field("auth_required", auth_required)
+            .field("data_connection_type", data_connection_type)
+            .field("data_type", data_type)
+            .field("character_encoding", character_encoding)
+            .field("encryption_mode", encryption_mode)
+            .field("list_method", list_method)
+            .field("default_path", default_path)
+            .field("force_single_connection", force_single_connection)
+            .field("data_stale_detection", data_stale_detection)
+            .field("special_server_mode", special_server_mode)
+            .field("legacy_elements", legacy_elements)
+            .field("extensions", extensions)
+            .finish()
+    }
+}
+
+impl SfdlPackage {
+    /// Total size in bytes of the files in this package's file list.
+    #[must_use]
+    pub fn total_size(&self) -> u64 {
+        self.file_list
+            .iter()
+            .flat_map(|list| &list.file_info)
+            .map(|file| file.file_size)
+            .sum()
+    }
 }
 
 impl FromStr for SfdlFile {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        quick_xml::de::from_str(s).map_err(ParseError::InvalidSfdlDeserialize)
+        let mut sfdl = version::from_str(s)?;
+        extensions::restore(&mut sfdl, s)?;
+        Ok(sfdl)
     }
 }
 
@@ -451,13 +1218,14 @@ impl Default for SfdlFile {
             xmlns_xsi: XMLNS_XSI.to_string(),
             description: String::new(),
             uploader: String::new(),
-            sfdlfile_version: 6,
+            sfdlfile_version: version::CURRENT_VERSION,
             encrypted: false,
             connection_info: ConnectionInfo::default(),
             packages: Packages {
                 package: vec![SfdlPackage::default()],
             },
             max_download_threads: 3,
+            extensions: Extensions::default(),
         }
     }
 }
@@ -467,18 +1235,20 @@ impl Default for ConnectionInfo {
         Self {
             host: String::new(),
             port: 21,
-            username: String::new(),
-            password: String::new(),
+            username: SecretString::default(),
+            password: SecretString::default(),
             auth_required: false,
             data_connection_type: DataConnectionType::AutoPassive,
             data_type: DataType::Binary,
             character_encoding: CharacterEncoding::Standard,
             encryption_mode: EncryptionMode::None,
-            list_method: "ForceList".to_string(),
+            list_method: ListMethod::ForceList,
             default_path: "/".to_string(),
             force_single_connection: false,
             data_stale_detection: true,
             special_server_mode: false,
+            legacy_elements: LegacyElements::default(),
+            extensions: Extensions::default(),
         }
     }
 }
@@ -490,6 +1260,116 @@ impl Default for SfdlPackage {
             bulk_folder_mode: true,
             bulk_folder_list: BulkFolderList::default(),
             file_list: None,
+            extensions: Extensions::default(),
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn sample_with(replacements: &[(&str, &str)]) -> String {
+        let mut xml = fs::read_to_string("tests/data/single_package_bulkfolder.xml").unwrap();
+        for (from, to) in replacements {
+            assert!(xml.contains(from), "{from}");
+            xml = xml.replace(from, to);
+        }
+        xml
+    }
+
+    #[test]
+    fn test_enums_match_case_insensitively() {
+        let xml = sample_with(&[
+            ("<CharacterEncoding>UTF8", "<CharacterEncoding>utf8"),
+            ("<DataType>Binary", "<DataType>BINARY"),
+            ("<EncryptionMode>None", "<EncryptionMode>none"),
+            ("<ListMethod>ForceList", "<ListMethod>forcelist"),
+        ]);
+        let sfdl: SfdlFile = xml.parse().unwrap();
+
+        let info = &sfdl.connection_info;
+        assert_eq!(info.character_encoding, CharacterEncoding::UTF8);
+        assert_eq!(info.data_type, DataType::Binary);
+        assert_eq!(info.encryption_mode, EncryptionMode::None);
+        assert_eq!(info.list_method, ListMethod::ForceList);
+
+        let written = sfdl.to_xml_string().unwrap();
+        assert!(written.contains("<CharacterEncoding>UTF8</CharacterEncoding>"));
+        assert!(written.contains("<ListMethod>ForceList</ListMethod>"));
+    }
+
+    #[test]
+    fn test_unknown_enum_values_round_trip() {
+        let xml = sample_with(&[
+            (
+                "<DataConnectionType>AutoPassive",
+                "<DataConnectionType>QuantumPassive",
+            ),
+            ("<EncryptionMode>None", "<EncryptionMode>ExplicitTLS13"),
+            ("<ListMethod>ForceList", "<ListMethod>UseMLSD"),
+        ]);
+        let sfdl: SfdlFile = xml.parse().unwrap();
+
+        let info = &sfdl.connection_info;
+        assert_eq!(
+            info.data_connection_type,
+            DataConnectionType::Other("QuantumPassive".to_string())
+        );
+        assert_eq!(
+            info.encryption_mode,
+            EncryptionMode::Other("ExplicitTLS13".to_string())
+        );
+        assert_eq!(info.list_method, ListMethod::Other("UseMLSD".to_string()));
+
+        let written = sfdl.to_xml_string().unwrap();
+        assert!(written.contains("<DataConnectionType>QuantumPassive</DataConnectionType>"));
+        assert!(written.contains("<EncryptionMode>ExplicitTLS13</EncryptionMode>"));
+        assert!(written.contains("<ListMethod>UseMLSD</ListMethod>"));
+        assert_eq!(written.parse::<SfdlFile>().unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_debug_masks_credentials() {
+        let sfdl = SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap();
+
+        let debug = format!("{sfdl:?}");
+        assert!(!debug.contains("listpass") && !debug.contains("listuser"));
+        assert!(debug.contains("password: [redacted]"));
+        assert!(debug.contains("ftp.example.com"));
+
+        let debug = format!("{:#?}", sfdl.redacted());
+        assert!(!debug.contains("ftp.example.com") && !debug.contains("listpass"));
+        assert!(debug.contains("File list description"));
+
+        // Apart from the host, the output matches the derived one.
+        let mut masked = sfdl.clone();
+        masked.connection_info.host = "[redacted]".to_string();
+        assert_eq!(
+            format!("{:#?}", sfdl.redacted()),
+            format!("{masked:#?}").replace("\"[redacted]\"", "[redacted]")
+        );
+    }
+
+    #[test]
+    fn test_display_summary() {
+        let mut sfdl = SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap();
+        assert_eq!(sfdl.total_size(), 1066);
+        assert_eq!(
+            sfdl.to_string(),
+            "File list description by File list uploader: 1 package, 1.0 KiB"
+        );
+
+        sfdl.uploader.clear();
+        sfdl.packages.push(SfdlPackage::default());
+        sfdl.packages[0].file_list.as_mut().unwrap().file_info[0].file_size = 3 << 30;
+        assert_eq!(
+            sfdl.to_string(),
+            "File list description: 2 packages, 3.0 GiB"
+        );
+
+        sfdl.packages.clear();
+        assert_eq!(sfdl.to_string(), "File list description: 0 packages, 0 B");
+    }
+}
@@ -0,0 +1,968 @@
+//! Pull-based reading of very large containers.
+//!
+//! [`SfdlFile::from_reader`](crate::SfdlFile::from_reader) keeps every
+//! `FileInfo` entry in memory, which adds up for generated containers with
+//! tens of thousands of files. [`SfdlReader`] reads the document
+//! incrementally instead and yields its parts as [`SfdlItem`]s in document
+//! order: the [`Header`], the [`ConnectionInfo`], then every package followed
+//! by its file entries, and finally [`SfdlItem::End`].
+//!
+//! The children of `SFDLFile` and `SFDLPackage` may appear in any order. In
+//! the order SFDL.NET writes them only the item being read is held in
+//! memory. Otherwise items are held back until they can be yielded in the
+//! order above: everything read before the header fields are complete, and
+//! the file entries of a package until its `Packagename` and `BulkFolderMode`
+//! have been read. A package without a `BulkFolderList` before its
+//! `FileList` gets an empty one, so a `BulkFolderList` after the `FileList`
+//! is an error.
+//!
+//! Unknown attributes and child elements are collected into the
+//! [`Extensions`] of the item they belong to, as
+//! [`SfdlFile::from_reader`](crate::SfdlFile::from_reader) does. Those of
+//! `SFDLFile` come with [`SfdlItem::End`], and unknown children of a package
+//! that follow its `FileList` come as [`SfdlItem::PackageExtensions`].
+//!
+//! Given a password, the reader decrypts each item as it is read. The
+//! [`Header`] it yields then reports the container as not encrypted.
+//!
+//! The reader expects UTF-8 input; use
+//! [`SfdlFile::from_bytes`](crate::SfdlFile::from_bytes) for containers in
+//! other encodings.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sfdl::stream::{SfdlItem, SfdlReader};
+//!
+//! let reader = SfdlReader::from_file("examples/encrypted.sfdl")
+//!     .unwrap()
+//!     .with_password("S3cr3tP4ssw0rd!");
+//!
+//! let mut files = 0;
+//! for item in reader {
+//!     match item.unwrap() {
+//!         SfdlItem::Header(header) => assert_eq!(header.description, "MyDescription"),
+//!         SfdlItem::FileInfo(_) => files += 1,
+//!         _ => {}
+//!     }
+//! }
+//! # let _ = files;
+//! ```
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::iter::FusedIterator;
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{DeError, Reader, Writer, XmlVersion};
+use serde::de::DeserializeOwned;
+
+use crate::crypto::{self, FieldPath, SfdlKey};
+use crate::error::{ParseError, SfdlError};
+use crate::extensions::{
+    self, is_known, unknown_attributes, ExtensionElement, Extensions, PACKAGE_ELEMENTS,
+    SFDL_FILE_ATTRIBUTES, SFDL_FILE_ELEMENTS,
+};
+use crate::sfdl::{
+    default_xmlns_xsd, default_xmlns_xsi, BulkFolderList, ConnectionInfo, FileInfo, FileList,
+    SfdlPackage,
+};
+use crate::version;
+
+/// The scalar fields of `SFDLFile`, except for `MaxDownloadThreads`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// XML namespace for XML Schema.
+    pub xmlns_xsd: String,
+    /// XML namespace for XML Schema instance.
+    pub xmlns_xsi: String,
+    /// Description of the container.
+    pub description: String,
+    /// Name of the uploader.
+    pub uploader: String,
+    /// File format version.
+    pub sfdlfile_version: u16,
+    /// Whether the items that follow are encrypted.
+    pub encrypted: bool,
+}
+
+/// A part of a container, as yielded by [`SfdlReader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SfdlItem {
+    /// The container header, always yielded first.
+    Header(Header),
+    /// The connection settings.
+    ConnectionInfo(ConnectionInfo),
+    /// A package without its file entries.
+    ///
+    /// [`SfdlPackage::file_list`] is an empty list if the package has a
+    /// `FileList`, whose entries follow as [`SfdlItem::FileInfo`] items, and
+    /// `None` otherwise. [`SfdlPackage::extensions`] holds the unknown
+    /// attributes and the unknown children read before the package was
+    /// yielded.
+    Package(SfdlPackage),
+    /// A file entry of the package yielded last.
+    FileInfo(FileInfo),
+    /// Unknown children of the package yielded last that follow its
+    /// `FileList`, yielded after its file entries if there are any.
+    ///
+    /// They belong at the end of [`Extensions::elements`] of that package.
+    PackageExtensions(Vec<ExtensionElement>),
+    /// The fields that follow the packages, always yielded last.
+    End {
+        /// Maximum number of concurrent downloads.
+        max_download_threads: u16,
+        /// Unknown attributes and child elements of `SFDLFile`.
+        extensions: Extensions,
+    },
+}
+
+/// Reads a container one [`SfdlItem`] at a time.
+///
+/// The reader stops after the first error.
+pub struct SfdlReader<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    key: Option<SfdlKey>,
+    state: State,
+    header: HeaderFields,
+    version: Option<u16>,
+    decrypt: bool,
+    held: Vec<Raw>,
+    package: Option<PackageFields>,
+    /// Whether the current package was yielded with an empty
+    /// `BulkFolderList` because its `FileList` came first.
+    bulk_folder_list_assumed: bool,
+    package_index: usize,
+    file_index: usize,
+    max_download_threads: Option<u16>,
+    extensions: Collected,
+    package_extensions: Collected,
+    pending: VecDeque<SfdlItem>,
+}
+
+/// The element the reader is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Document,
+    SfdlFile,
+    Packages,
+    Package,
+    FileList,
+    Done,
+}
+
+#[derive(Default)]
+struct HeaderFields {
+    xmlns_xsd: Option<String>,
+    xmlns_xsi: Option<String>,
+    description: Option<String>,
+    uploader: Option<String>,
+    sfdlfile_version: Option<u16>,
+    encrypted: Option<bool>,
+}
+
+impl HeaderFields {
+    fn is_complete(&self) -> bool {
+        self.description.is_some()
+            && self.uploader.is_some()
+            && self.sfdlfile_version.is_some()
+            && self.encrypted.is_some()
+    }
+}
+
+/// The fields of the package being read, until it is yielded.
+#[derive(Default)]
+struct PackageFields {
+    package_name: Option<String>,
+    bulk_folder_mode: Option<bool>,
+    bulk_folder_list: Option<BulkFolderList>,
+    /// The entries of a `FileList` that was read before the other fields.
+    files: Option<Vec<FileInfo>>,
+}
+
+impl PackageFields {
+    /// Returns `true` if the package can be yielded before its file entries.
+    fn is_complete(&self) -> bool {
+        self.package_name.is_some() && self.bulk_folder_mode.is_some()
+    }
+}
+
+/// The extensions of an element being read.
+#[derive(Default)]
+struct Collected {
+    extensions: Extensions,
+    /// Name of the last known child read.
+    after: Option<String>,
+}
+
+/// An item that has been read but not decrypted yet.
+enum Raw {
+    /// The XML of `ConnectionInfo`, whose layout depends on the file version.
+    ConnectionInfo(String),
+    Package(usize, SfdlPackage),
+    FileInfo(usize, usize, FileInfo),
+    PackageExtensions(Vec<ExtensionElement>),
+}
+
+impl SfdlReader<BufReader<File>> {
+    /// Opens the container at `path` for reading.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SfdlError> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> SfdlReader<R> {
+    /// Creates a reader that yields the items of the container in `reader`.
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Reader::from_reader(reader),
+            buf: Vec::new(),
+            key: None,
+            state: State::Document,
+            header: HeaderFields::default(),
+            version: None,
+            decrypt: false,
+            held: Vec::new(),
+            package: None,
+            bulk_folder_list_assumed: false,
+            package_index: 0,
+            file_index: 0,
+            max_download_threads: None,
+            extensions: Collected::default(),
+            package_extensions: Collected::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Decrypts the items of an encrypted container with `password`.
+    #[must_use]
+    pub fn with_password(self, password: &str) -> Self {
+        self.with_key(SfdlKey::from_password(password))
+    }
+
+    /// Decrypts the items of an encrypted container with `key`.
+    #[must_use]
+    pub fn with_key(mut self, key: SfdlKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    fn read_item(&mut self) -> Result<Option<SfdlItem>, SfdlError> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Ok(Some(item));
+            }
+            if self.state == State::Done {
+                return Ok(None);
+            }
+
+            self.buf.clear();
+            let event = self.reader.read_event_into(&mut self.buf);
+            match event.map_err(xml_error)?.into_owned() {
+                Event::Start(element) => self.start(element, false)?,
+                Event::Empty(element) => self.start(element, true)?,
+                Event::End(_) => self.end()?,
+                Event::Eof => return Err(de_error(DeError::UnexpectedEof)),
+                _ => {}
+            }
+        }
+    }
+
+    fn start(&mut self, element: BytesStart<'static>, empty: bool) -> Result<(), SfdlError> {
+        if let Some((known, _)) = self.collected() {
+            if !is_known(known, element.name().as_ref()) {
+                let xml = self.capture(element, empty)?;
+                if let Some((_, collected)) = self.collected() {
+                    collected.extensions.elements.push(ExtensionElement {
+                        after: collected.after.clone(),
+                        xml,
+                    });
+                }
+                return Ok(());
+            }
+        }
+        if let Some((_, collected)) = self.collected() {
+            collected.after = Some(String::from_utf8_lossy(element.name().as_ref()).into_owned());
+        }
+
+        let name = element.local_name();
+        match (self.state, name.as_ref()) {
+            (State::Document, b"SFDLFile") => {
+                let header = &mut self.header;
+                for attribute in element.attributes() {
+                    let attribute = attribute.map_err(|err| xml_error(err.into()))?;
+                    let value = attribute
+                        .normalized_value(XmlVersion::Implicit1_0)
+                        .map_err(xml_error)?
+                        .into_owned();
+                    match attribute.key.as_ref() {
+                        b"xmlns:xsd" => header.xmlns_xsd = Some(value),
+                        b"xmlns:xsi" => header.xmlns_xsi = Some(value),
+                        _ => {}
+                    }
+                }
+                self.extensions.extensions.attributes =
+                    unknown_attributes(&element, SFDL_FILE_ATTRIBUTES)?;
+                self.enter(State::SfdlFile, empty)?;
+            }
+            (State::Document, _) => {
+                return Err(de_error(DeError::Custom(
+                    "expected `SFDLFile` root element".to_string(),
+                )));
+            }
+            (State::SfdlFile, b"Description") => {
+                let value = self.value(element, empty)?;
+                set(Some(&mut self.header.description), value, "Description")?;
+                self.flush_header(false)?;
+            }
+            (State::SfdlFile, b"Uploader") => {
+                let value = self.value(element, empty)?;
+                set(Some(&mut self.header.uploader), value, "Uploader")?;
+                self.flush_header(false)?;
+            }
+            (State::SfdlFile, b"SFDLFileVersion") => {
+                let value = self.value(element, empty)?;
+                set(
+                    Some(&mut self.header.sfdlfile_version),
+                    value,
+                    "SFDLFileVersion",
+                )?;
+                self.flush_header(false)?;
+            }
+            (State::SfdlFile, b"Encrypted") => {
+                let value = self.value(element, empty)?;
+                set(Some(&mut self.header.encrypted), value, "Encrypted")?;
+                self.flush_header(false)?;
+            }
+            (State::SfdlFile, b"MaxDownloadThreads") => {
+                let value = self.value(element, empty)?;
+                set(
+                    Some(&mut self.max_download_threads),
+                    value,
+                    "MaxDownloadThreads",
+                )?;
+            }
+            (State::SfdlFile, b"ConnectionInfo") => {
+                let xml = self.capture(element, empty)?;
+                self.emit(Raw::ConnectionInfo(xml))?;
+            }
+            (State::SfdlFile, b"Packages") => self.enter(State::Packages, empty)?,
+            (State::Packages, b"SFDLPackage") => {
+                self.package = Some(PackageFields::default());
+                self.bulk_folder_list_assumed = false;
+                self.package_extensions = Collected::default();
+                self.package_extensions.extensions.attributes = unknown_attributes(&element, &[])?;
+                self.file_index = 0;
+                self.enter(State::Package, empty)?;
+            }
+            (State::Package, b"Packagename") => {
+                let value = self.value(element, empty)?;
+                let slot = self.package.as_mut().map(|fields| &mut fields.package_name);
+                set(slot, value, "Packagename")?;
+            }
+            (State::Package, b"BulkFolderMode") => {
+                let value = self.value(element, empty)?;
+                let slot = self
+                    .package
+                    .as_mut()
+                    .map(|fields| &mut fields.bulk_folder_mode);
+                set(slot, value, "BulkFolderMode")?;
+            }
+            (State::Package, b"BulkFolderList") => {
+                if self.bulk_folder_list_assumed {
+                    return Err(de_error(DeError::Custom(
+                        "`BulkFolderList` after `FileList`".to_string(),
+                    )));
+                }
+                let value = self.value(element, empty)?;
+                let slot = self
+                    .package
+                    .as_mut()
+                    .map(|fields| &mut fields.bulk_folder_list);
+                set(slot, value, "BulkFolderList")?;
+            }
+            (State::Package, b"FileList") => {
+                let slot = self.package.as_mut().map(|fields| &mut fields.files);
+                set(slot, Vec::new(), "FileList")?;
+                if let Some(fields) = self.package.as_mut().filter(|fields| fields.is_complete()) {
+                    // The entries are not held back for a `BulkFolderList`
+                    // that may never come.
+                    self.bulk_folder_list_assumed = fields.bulk_folder_list.is_none();
+                    self.flush_package()?;
+                }
+                self.enter(State::FileList, empty)?;
+            }
+            (State::FileList, b"FileInfo") => {
+                let file: FileInfo = self.value(element, empty)?;
+                let index = self.file_index;
+                self.file_index += 1;
+                match self
+                    .package
+                    .as_mut()
+                    .and_then(|fields| fields.files.as_mut())
+                {
+                    Some(files) => files.push(file),
+                    None => self.emit(Raw::FileInfo(self.package_index, index, file))?,
+                }
+            }
+            _ => self.skip(&element, empty)?,
+        }
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), SfdlError> {
+        match self.state {
+            State::SfdlFile => {
+                self.flush_header(true)?;
+                let max_download_threads = self
+                    .max_download_threads
+                    .ok_or_else(|| missing("MaxDownloadThreads"))?;
+                self.pending.push_back(SfdlItem::End {
+                    max_download_threads,
+                    extensions: std::mem::take(&mut self.extensions.extensions),
+                });
+                self.state = State::Done;
+            }
+            State::Packages => self.state = State::SfdlFile,
+            State::Package => {
+                if self.package.is_some() {
+                    self.flush_package()?;
+                } else {
+                    let elements = std::mem::take(&mut self.package_extensions.extensions.elements);
+                    if !elements.is_empty() {
+                        self.emit(Raw::PackageExtensions(elements))?;
+                    }
+                }
+                self.package_index += 1;
+                self.state = State::Packages;
+            }
+            State::FileList => self.state = State::Package,
+            State::Document | State::Done => {}
+        }
+
+        Ok(())
+    }
+
+    /// Moves into the element `state` stands for, and right out of it again
+    /// if the element is empty.
+    fn enter(&mut self, state: State, empty: bool) -> Result<(), SfdlError> {
+        self.state = state;
+        if empty {
+            self.end()?;
+        }
+        Ok(())
+    }
+
+    /// The known children and the extensions of the element being read, if
+    /// it keeps extensions.
+    ///
+    /// `ConnectionInfo` is read in one piece, its extensions are collected
+    /// when it is parsed.
+    fn collected(&mut self) -> Option<(&'static [&'static str], &mut Collected)> {
+        match self.state {
+            State::SfdlFile => Some((SFDL_FILE_ELEMENTS, &mut self.extensions)),
+            State::Package => Some((PACKAGE_ELEMENTS, &mut self.package_extensions)),
+            _ => None,
+        }
+    }
+
+    /// The key to decrypt items with, if the container is encrypted.
+    fn active_key(&self) -> Option<&SfdlKey> {
+        self.key.as_ref().filter(|_| self.decrypt)
+    }
+
+    /// Yields the header once all of its fields have been read, followed by
+    /// the items held back until then.
+    ///
+    /// With `required`, a missing field is an error instead of a reason to
+    /// wait.
+    fn flush_header(&mut self, required: bool) -> Result<(), SfdlError> {
+        if self.version.is_some() {
+            return Ok(());
+        }
+        let fields = &self.header;
+        if !required && !fields.is_complete() {
+            return Ok(());
+        }
+
+        let mut header = Header {
+            xmlns_xsd: fields.xmlns_xsd.clone().unwrap_or_else(default_xmlns_xsd),
+            xmlns_xsi: fields.xmlns_xsi.clone().unwrap_or_else(default_xmlns_xsi),
+            description: fields
+                .description
+                .clone()
+                .ok_or_else(|| missing("Description"))?,
+            uploader: fields.uploader.clone().ok_or_else(|| missing("Uploader"))?,
+            sfdlfile_version: fields
+                .sfdlfile_version
+                .ok_or_else(|| missing("SFDLFileVersion"))?,
+            encrypted: fields.encrypted.ok_or_else(|| missing("Encrypted"))?,
+        };
+
+        self.decrypt = header.encrypted && self.key.is_some();
+        if let Some(key) = self.active_key() {
+            crypto::decrypt_field(key, FieldPath::Root("description"), &mut header.description)?;
+            crypto::decrypt_field(key, FieldPath::Root("uploader"), &mut header.uploader)?;
+            header.encrypted = false;
+        }
+
+        self.version = Some(header.sfdlfile_version);
+        self.pending.push_back(SfdlItem::Header(header));
+        for raw in std::mem::take(&mut self.held) {
+            self.release(raw)?;
+        }
+        Ok(())
+    }
+
+    /// Yields the current package, followed by the file entries held back
+    /// for it.
+    fn flush_package(&mut self) -> Result<(), SfdlError> {
+        let fields = self.package.take().unwrap_or_default();
+        let package = SfdlPackage {
+            package_name: fields.package_name.ok_or_else(|| missing("Packagename"))?,
+            bulk_folder_mode: fields
+                .bulk_folder_mode
+                .ok_or_else(|| missing("BulkFolderMode"))?,
+            bulk_folder_list: fields.bulk_folder_list.unwrap_or_default(),
+            file_list: fields.files.as_ref().map(|_| FileList::default()),
+            extensions: std::mem::take(&mut self.package_extensions.extensions),
+        };
+
+        self.emit(Raw::Package(self.package_index, package))?;
+        for (index, file) in fields.files.into_iter().flatten().enumerate() {
+            self.emit(Raw::FileInfo(self.package_index, index, file))?;
+        }
+        Ok(())
+    }
+
+    /// Yields `raw`, or holds it back until the header has been yielded.
+    fn emit(&mut self, raw: Raw) -> Result<(), SfdlError> {
+        if self.version.is_some() {
+            self.release(raw)
+        } else {
+            self.held.push(raw);
+            Ok(())
+        }
+    }
+
+    /// Parses and decrypts `raw` as needed and yields it.
+    fn release(&mut self, raw: Raw) -> Result<(), SfdlError> {
+        let version = self.version.unwrap_or(version::CURRENT_VERSION);
+        let key = self.active_key();
+        let mut decrypt = |path: FieldPath, field: &mut String| match key {
+            Some(key) => crypto::decrypt_field(key, path, field),
+            None => Ok(()),
+        };
+
+        let item = match raw {
+            Raw::ConnectionInfo(xml) => {
+                let mut info =
+                    version::connection_info_from_str(&xml, version).map_err(de_error)?;
+                extensions::restore_connection_info(&mut info, &xml)?;
+                crypto::visit_connection_fields(&mut info, &mut decrypt)?;
+                SfdlItem::ConnectionInfo(info)
+            }
+            Raw::Package(index, mut package) => {
+                crypto::visit_package_fields(index, &mut package, &mut decrypt)?;
+                SfdlItem::Package(package)
+            }
+            Raw::FileInfo(package, index, mut file) => {
+                crypto::visit_file_fields(package, index, &mut file, &mut decrypt)?;
+                SfdlItem::FileInfo(file)
+            }
+            Raw::PackageExtensions(elements) => SfdlItem::PackageExtensions(elements),
+        };
+        self.pending.push_back(item);
+        Ok(())
+    }
+
+    /// Deserializes the element that starts with `element`.
+    fn value<T: DeserializeOwned>(
+        &mut self,
+        element: BytesStart<'static>,
+        empty: bool,
+    ) -> Result<T, SfdlError> {
+        let xml = self.capture(element, empty)?;
+        quick_xml::de::from_str(&xml).map_err(de_error)
+    }
+
+    /// Reads the rest of the element that starts with `element` and returns
+    /// it as a standalone document.
+    fn capture(&mut self, element: BytesStart<'static>, empty: bool) -> Result<String, SfdlError> {
+        let mut writer = Writer::new(Vec::new());
+        if empty {
+            writer.write_event(Event::Empty(element))?;
+        } else {
+            writer.write_event(Event::Start(element))?;
+            let mut depth = 0usize;
+            loop {
+                self.buf.clear();
+                let event = self
+                    .reader
+                    .read_event_into(&mut self.buf)
+                    .map_err(xml_error)?;
+                match &event {
+                    Event::Start(_) => depth += 1,
+                    Event::End(_) if depth == 0 => {
+                        writer.write_event(event)?;
+                        break;
+                    }
+                    Event::End(_) => depth -= 1,
+                    Event::Eof => return Err(de_error(DeError::UnexpectedEof)),
+                    _ => {}
+                }
+                writer.write_event(event)?;
+            }
+        }
+
+        String::from_utf8(writer.into_inner())
+            .map_err(|_| ParseError::MalformedInput { encoding: "UTF-8" }.into())
+    }
+
+    /// Skips the element that starts with `element`.
+    fn skip(&mut self, element: &BytesStart<'_>, empty: bool) -> Result<(), SfdlError> {
+        if !empty {
+            self.buf.clear();
+            self.reader
+                .read_to_end_into(element.name(), &mut self.buf)
+                .map_err(xml_error)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for SfdlReader<R> {
+    type Item = Result<SfdlItem, SfdlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_item() {
+            Ok(item) => item.map(Ok),
+            Err(err) => {
+                self.state = State::Done;
+                self.pending.clear();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> FusedIterator for SfdlReader<R> {}
+
+/// Stores `value` in `slot`, which is `None` if the element it belongs to has
+/// been yielded already.
+fn set<T>(slot: Option<&mut Option<T>>, value: T, field: &str) -> Result<(), SfdlError> {
+    match slot {
+        Some(slot @ None) => {
+            *slot = Some(value);
+            Ok(())
+        }
+        _ => Err(de_error(DeError::Custom(format!(
+            "duplicate field `{field}`"
+        )))),
+    }
+}
+
+fn missing(field: &str) -> SfdlError {
+    de_error(DeError::Custom(format!("missing field `{field}`")))
+}
+
+fn de_error(err: DeError) -> SfdlError {
+    ParseError::InvalidSfdlDeserialize(err).into()
+}
+
+fn xml_error(err: quick_xml::Error) -> SfdlError {
+    de_error(err.into())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::sfdl::SfdlFile;
+
+    /// Builds a container from the items of `reader`.
+    fn collect<R: BufRead>(reader: SfdlReader<R>) -> Result<SfdlFile, SfdlError> {
+        let mut sfdl = SfdlFile::default();
+        sfdl.packages.clear();
+        for item in reader {
+            match item? {
+                SfdlItem::Header(header) => {
+                    sfdl.xmlns_xsd = header.xmlns_xsd;
+                    sfdl.xmlns_xsi = header.xmlns_xsi;
+                    sfdl.description = header.description;
+                    sfdl.uploader = header.uploader;
+                    sfdl.sfdlfile_version = header.sfdlfile_version;
+                    sfdl.encrypted = header.encrypted;
+                }
+                SfdlItem::ConnectionInfo(info) => sfdl.connection_info = info,
+                SfdlItem::Package(package) => sfdl.packages.push(package),
+                SfdlItem::FileInfo(file) => {
+                    let package = sfdl.packages.last_mut().unwrap();
+                    package.file_list.as_mut().unwrap().file_info.push(file);
+                }
+                SfdlItem::PackageExtensions(elements) => {
+                    let package = sfdl.packages.last_mut().unwrap();
+                    package.extensions.elements.extend(elements);
+                }
+                SfdlItem::End {
+                    max_download_threads,
+                    extensions,
+                } => {
+                    sfdl.max_download_threads = max_download_threads;
+                    sfdl.extensions = extensions;
+                }
+            }
+        }
+        Ok(sfdl)
+    }
+
+    fn read(xml: &str) -> Result<SfdlFile, SfdlError> {
+        collect(SfdlReader::new(xml.as_bytes()))
+    }
+
+    #[test]
+    fn test_matches_from_file() {
+        for path in [
+            "tests/data/filelist_mode.xml",
+            "tests/data/mixed_mode.xml",
+            "tests/data/multi_package_bulkfolders.xml",
+            "tests/data/legacy_v3.xml",
+            "examples/encrypted.sfdl",
+        ] {
+            let streamed = collect(SfdlReader::from_file(path).unwrap()).unwrap();
+            assert_eq!(streamed, SfdlFile::from_file(path).unwrap(), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_item_order() {
+        let items: Vec<_> = SfdlReader::from_file("tests/data/filelist_mode.xml")
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(items.len(), 6);
+        assert!(matches!(items[0], SfdlItem::Header(_)));
+        assert!(matches!(items[1], SfdlItem::ConnectionInfo(_)));
+        let SfdlItem::Package(package) = &items[2] else {
+            panic!("expected a package");
+        };
+        assert_eq!(package.file_list, Some(FileList::default()));
+        let SfdlItem::FileInfo(file) = &items[4] else {
+            panic!("expected a file");
+        };
+        assert_eq!(file.file_size, 1024);
+        assert_eq!(
+            items[5],
+            SfdlItem::End {
+                max_download_threads: 3,
+                extensions: Extensions::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_decrypts_items() {
+        let password = "S3cr3tP4ssw0rd!";
+        let reader = SfdlReader::from_file("examples/encrypted.sfdl")
+            .unwrap()
+            .with_password(password);
+        let streamed = collect(reader).unwrap();
+
+        let mut expected = SfdlFile::from_file("examples/encrypted.sfdl").unwrap();
+        expected.decrypt(password).unwrap();
+        assert_eq!(streamed, expected);
+
+        let plain = SfdlReader::from_file("tests/data/filelist_mode.xml")
+            .unwrap()
+            .with_password(password);
+        assert_eq!(
+            collect(plain).unwrap(),
+            SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_wrong_password_stops() {
+        let mut reader = SfdlReader::from_file("examples/encrypted.sfdl")
+            .unwrap()
+            .with_password("wrong");
+
+        let err = reader.next().unwrap().unwrap_err();
+        let SfdlError::Decrypt(err) = err else {
+            panic!("expected a decryption error");
+        };
+        assert_eq!(err.path(), Some("description"));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let xml = std::fs::read_to_string("tests/data/filelist_mode.xml").unwrap();
+
+        let truncated = &xml[..xml.find("<FileSize>").unwrap()];
+        assert!(matches!(
+            read(truncated),
+            Err(SfdlError::Parse(ParseError::InvalidSfdlDeserialize(_)))
+        ));
+
+        let without_uploader = xml.replace("<Uploader>File list uploader</Uploader>", "");
+        let err = read(&without_uploader).unwrap_err();
+        assert!(matches!(
+            err,
+            SfdlError::Parse(ParseError::InvalidSfdlDeserialize(DeError::Custom(ref msg)))
+                if msg == "missing field `Uploader`"
+        ));
+
+        assert!(read("<Other/>").is_err());
+    }
+
+    #[test]
+    fn test_reordered_children() {
+        let xml = std::fs::read_to_string("tests/data/filelist_mode.xml").unwrap();
+        let package_fields = "<Packagename>FileListPackage</Packagename>\n            \
+                              <BulkFolderMode>false</BulkFolderMode>";
+        let header_fields = "<Description>File list description</Description>";
+        let reordered = xml
+            .replace(package_fields, "")
+            .replace("</FileList>", &format!("</FileList>{package_fields}"))
+            .replace(header_fields, "")
+            .replace(
+                "</ConnectionInfo>",
+                &format!("</ConnectionInfo>{header_fields}"),
+            );
+        assert!(xml.contains(package_fields) && xml.contains(header_fields));
+        assert!(SfdlFile::validate_schema(&reordered).is_empty());
+
+        let expected: SfdlFile = reordered.parse().unwrap();
+        assert_eq!(expected, SfdlFile::from_str(&xml).unwrap());
+        assert_eq!(read(&reordered).unwrap(), expected);
+
+        let items: Vec<_> = SfdlReader::new(reordered.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert!(matches!(items[0], SfdlItem::Header(_)));
+        assert!(matches!(items[1], SfdlItem::ConnectionInfo(_)));
+        assert!(matches!(items[2], SfdlItem::Package(_)));
+        assert!(matches!(items[3], SfdlItem::FileInfo(_)));
+
+        // Items read before `Encrypted` are decrypted once it is known.
+        let password = "S3cr3tP4ssw0rd!";
+        let encrypted = std::fs::read_to_string("examples/encrypted.sfdl")
+            .unwrap()
+            .replace("<Encrypted>true</Encrypted>", "")
+            .replace("</SFDLFile>", "<Encrypted>true</Encrypted></SFDLFile>");
+        let mut expected: SfdlFile = encrypted.parse().unwrap();
+        expected.decrypt(password).unwrap();
+        let reader = SfdlReader::new(encrypted.as_bytes()).with_password(password);
+        assert_eq!(collect(reader).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_duplicate_fields() {
+        let xml = std::fs::read_to_string("tests/data/filelist_mode.xml").unwrap();
+
+        for (element, duplicate) in [
+            ("<Uploader>", "<Uploader>again</Uploader>"),
+            ("</SFDLPackage>", "<Packagename>again</Packagename>"),
+        ] {
+            let duplicated = xml.replace(element, &format!("{duplicate}{element}"));
+            let field = &duplicate[1..duplicate.find('>').unwrap()];
+            let err = read(&duplicated).unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    SfdlError::Parse(ParseError::InvalidSfdlDeserialize(DeError::Custom(ref msg)))
+                        if *msg == format!("duplicate field `{field}`")
+                ),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_collects_unknown_elements() {
+        let xml = std::fs::read_to_string("tests/data/filelist_mode.xml")
+            .unwrap()
+            .replace("<SFDLFile ", "<SFDLFile xmlns:x=\"urn:x\" ")
+            .replace(
+                "<Uploader>",
+                "<Generator><Name>tool</Name></Generator><Uploader>",
+            )
+            .replace("<Port>", "<x:Proxy kind=\"socks\"/><Port>")
+            .replace("<SFDLPackage>", "<SFDLPackage Priority=\"high\"><First/>")
+            .replace("</FileList>", "</FileList><Last>a &amp; b</Last>")
+            .replacen("<FileInfo>", "<Note/><FileInfo>", 1)
+            .replace("</SFDLFile>", "<Trailer/></SFDLFile>");
+
+        let expected: SfdlFile = xml.parse().unwrap();
+        assert!(!expected.extensions.is_empty());
+        assert!(!expected.connection_info.extensions.is_empty());
+        assert_eq!(expected.packages[0].extensions.elements.len(), 2);
+
+        let streamed = read(&xml).unwrap();
+        assert_eq!(streamed, expected);
+        assert_eq!(
+            streamed.to_xml_string().unwrap(),
+            expected.to_xml_string().unwrap()
+        );
+
+        let items: Vec<_> = SfdlReader::new(xml.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        let SfdlItem::Package(package) = &items[2] else {
+            panic!("expected a package");
+        };
+        assert_eq!(package.extensions.attributes.len(), 1);
+        assert_eq!(
+            package.extensions.elements,
+            [ExtensionElement::new(None, "<First/>")]
+        );
+        assert_eq!(
+            items[5],
+            SfdlItem::PackageExtensions(vec![ExtensionElement::new(
+                Some("FileList"),
+                "<Last>a &amp; b</Last>"
+            )])
+        );
+    }
+
+    #[test]
+    fn test_missing_bulk_folder_list() {
+        let xml = std::fs::read_to_string("tests/data/filelist_mode.xml")
+            .unwrap()
+            .replace("<BulkFolderList></BulkFolderList>", "");
+        assert_eq!(read(&xml).unwrap(), xml.parse::<SfdlFile>().unwrap());
+
+        // The package and its first entry are yielded before the rest of the
+        // package has been read.
+        let truncated = &xml[..xml.find("</FileInfo>").unwrap() + "</FileInfo>".len()];
+        let items: Vec<_> = SfdlReader::new(truncated.as_bytes()).collect();
+        assert_eq!(items.len(), 5);
+        assert!(matches!(items[2], Ok(SfdlItem::Package(_))));
+        assert!(matches!(items[3], Ok(SfdlItem::FileInfo(_))));
+        assert!(items[4].is_err());
+
+        let late = xml.replace("</FileList>", "</FileList><BulkFolderList/>");
+        let err = read(&late).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                SfdlError::Parse(ParseError::InvalidSfdlDeserialize(DeError::Custom(ref msg)))
+                    if msg == "`BulkFolderList` after `FileList`"
+            ),
+            "{err}"
+        );
+    }
+}
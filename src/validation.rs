@@ -0,0 +1,616 @@
+//! Semantic validation of SFDL containers.
+//!
+//! A container that deserializes is not necessarily usable: it may point at
+//! port `0`, have an empty host, or list files whose full path does not match
+//! their directory and name. [`SfdlFile::validate`] walks the whole model and
+//! returns a [`Diagnostic`] for every problem it finds.
+//!
+//! Every diagnostic carries a [`Severity`], a stable [`DiagnosticCode`] and the
+//! path of the offending field, e.g. `packages[0].bulk_folder_list`.
+//!
+//! Documents that do not deserialize at all can be checked against the schema
+//! with [`SfdlFile::validate_schema`], see the [`schema`](crate::schema)
+//! module.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use sfdl::SfdlFile;
+//! # use sfdl::validation::Severity;
+//! let sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+//!
+//! for diagnostic in sfdl.validate() {
+//!     println!("{diagnostic}");
+//! }
+//!
+//! let has_errors = sfdl
+//!     .validate()
+//!     .iter()
+//!     .any(|diagnostic| diagnostic.severity == Severity::Error);
+//! assert!(!has_errors);
+//! ```
+//!
+//! # Encrypted containers
+//!
+//! Checks that look at the content of encryptable fields (empty host, path
+//! consistency, package names) are skipped while [`SfdlFile::encrypted`] is
+//! `true`, because those fields hold ciphertext. Decrypt the container first
+//! to validate them.
+
+use std::fmt;
+
+use crate::sfdl::{BulkFolder, ConnectionInfo, FileInfo, SfdlFile, SfdlPackage};
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The container is unusual but can still be processed.
+    Warning,
+    /// The container cannot be processed as is.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => f.write_str("warning"),
+            Self::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// Stable identifier of a validation rule.
+///
+/// The string returned by [`DiagnosticCode::as_str`] never changes between
+/// releases and can be matched on by tooling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DiagnosticCode {
+    /// `SFDLFileVersion` is `0`.
+    InvalidVersion,
+    /// `MaxDownloadThreads` is `0`.
+    InvalidThreadCount,
+    /// The container has no packages.
+    NoPackages,
+    /// `ConnectionInfo.Host` is empty.
+    EmptyHost,
+    /// `ConnectionInfo.Port` is `0`.
+    InvalidPort,
+    /// Authentication is required but no username is set.
+    MissingUsername,
+    /// `SFDLPackage.Packagename` is empty.
+    EmptyPackageName,
+    /// `BulkFolderMode` is `true` but `BulkFolderList` is empty.
+    EmptyBulkFolderList,
+    /// `BulkFolderMode` is `false` but there is no `FileInfo` entry.
+    EmptyFileList,
+    /// `BulkFolder.BulkFolderPath` is empty.
+    EmptyBulkFolderPath,
+    /// `FileInfo.FileName` is empty.
+    EmptyFileName,
+    /// `FileInfo.FileFullPath` is not `DirectoryPath` joined with `FileName`.
+    FilePathMismatch,
+    /// A `PackageName` differs from the name of its parent package.
+    PackageNameMismatch,
+    /// The document is not well-formed XML.
+    MalformedXml,
+    /// A required element is missing.
+    MissingElement,
+    /// An element appears where the schema does not expect it.
+    UnexpectedElement,
+    /// An element that may appear once appears more than once.
+    DuplicateElement,
+    /// The content of an element does not match its type.
+    InvalidValue,
+    /// The content of an element is not one of its known values.
+    UnknownValue,
+}
+
+impl DiagnosticCode {
+    /// Returns the stable string form of this code.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidVersion => "invalid-version",
+            Self::InvalidThreadCount => "invalid-thread-count",
+            Self::NoPackages => "no-packages",
+            Self::EmptyHost => "empty-host",
+            Self::InvalidPort => "invalid-port",
+            Self::MissingUsername => "missing-username",
+            Self::EmptyPackageName => "empty-package-name",
+            Self::EmptyBulkFolderList => "empty-bulk-folder-list",
+            Self::EmptyFileList => "empty-file-list",
+            Self::EmptyBulkFolderPath => "empty-bulk-folder-path",
+            Self::EmptyFileName => "empty-file-name",
+            Self::FilePathMismatch => "file-path-mismatch",
+            Self::PackageNameMismatch => "package-name-mismatch",
+            Self::MalformedXml => "malformed-xml",
+            Self::MissingElement => "missing-element",
+            Self::UnexpectedElement => "unexpected-element",
+            Self::DuplicateElement => "duplicate-element",
+            Self::InvalidValue => "invalid-value",
+            Self::UnknownValue => "unknown-value",
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single problem found by a validation pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// Stable identifier of the violated rule.
+    pub code: DiagnosticCode,
+    /// Path of the offending field, e.g. `connection_info.port`, or of the
+    /// offending element for [`SfdlFile::validate_schema`], e.g.
+    /// `/SFDLFile/ConnectionInfo/Port`.
+    pub path: String,
+    /// Human readable description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}] {}: {}",
+            self.severity, self.code, self.path, self.message
+        )
+    }
+}
+
+/// Collects diagnostics while walking the model.
+struct Validator {
+    /// Whether encryptable fields hold ciphertext and must not be inspected.
+    encrypted: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn new(encrypted: bool) -> Self {
+        Self {
+            encrypted,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: DiagnosticCode,
+        path: String,
+        message: impl Into<String>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            path,
+            message: message.into(),
+        });
+    }
+
+    fn sfdl_file(&mut self, sfdl: &SfdlFile) {
+        if sfdl.sfdlfile_version == 0 {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::InvalidVersion,
+                "sfdlfile_version".to_string(),
+                "file version must not be 0",
+            );
+        }
+        if sfdl.max_download_threads == 0 {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::InvalidThreadCount,
+                "max_download_threads".to_string(),
+                "at least one download thread is required",
+            );
+        }
+
+        self.connection_info(&sfdl.connection_info, "connection_info.");
+
+        if sfdl.packages.is_empty() {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::NoPackages,
+                "packages".to_string(),
+                "container has no packages",
+            );
+        }
+        for (i, package) in sfdl.packages.iter().enumerate() {
+            self.package(package, &format!("packages[{i}]."));
+        }
+    }
+
+    fn connection_info(&mut self, info: &ConnectionInfo, prefix: &str) {
+        if !self.encrypted && info.host.is_empty() {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::EmptyHost,
+                format!("{prefix}host"),
+                "host must not be empty",
+            );
+        }
+        if info.port == 0 {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::InvalidPort,
+                format!("{prefix}port"),
+                "port must not be 0",
+            );
+        }
+        if !self.encrypted && info.auth_required && info.username.is_empty() {
+            self.push(
+                Severity::Warning,
+                DiagnosticCode::MissingUsername,
+                format!("{prefix}username"),
+                "authentication is required but no username is set",
+            );
+        }
+    }
+
+    fn package(&mut self, package: &SfdlPackage, prefix: &str) {
+        if !self.encrypted && package.package_name.is_empty() {
+            self.push(
+                Severity::Warning,
+                DiagnosticCode::EmptyPackageName,
+                format!("{prefix}package_name"),
+                "package name is empty",
+            );
+        }
+
+        let folders = &package.bulk_folder_list.bulk_folder;
+        if package.bulk_folder_mode && folders.is_empty() {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::EmptyBulkFolderList,
+                format!("{prefix}bulk_folder_list"),
+                "bulk folder mode is enabled but the bulk folder list is empty",
+            );
+        }
+        for (i, folder) in folders.iter().enumerate() {
+            self.bulk_folder(
+                folder,
+                &package.package_name,
+                &format!("{prefix}bulk_folder_list.bulk_folder[{i}]."),
+            );
+        }
+
+        let files = package
+            .file_list
+            .as_ref()
+            .map_or(&[][..], |list| &list.file_info);
+        if !package.bulk_folder_mode && files.is_empty() {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::EmptyFileList,
+                format!("{prefix}file_list"),
+                "bulk folder mode is disabled but the file list is empty",
+            );
+        }
+        for (i, file) in files.iter().enumerate() {
+            self.file_info(
+                file,
+                &package.package_name,
+                &format!("{prefix}file_list.file_info[{i}]."),
+            );
+        }
+    }
+
+    fn bulk_folder(&mut self, folder: &BulkFolder, package_name: &str, prefix: &str) {
+        if self.encrypted {
+            return;
+        }
+        if folder.bulk_folder_path.is_empty() {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::EmptyBulkFolderPath,
+                format!("{prefix}bulk_folder_path"),
+                "bulk folder path must not be empty",
+            );
+        }
+        self.package_name(&folder.package_name, package_name, prefix);
+    }
+
+    fn file_info(&mut self, file: &FileInfo, package_name: &str, prefix: &str) {
+        if self.encrypted {
+            return;
+        }
+        if file.file_name.is_empty() {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::EmptyFileName,
+                format!("{prefix}file_name"),
+                "file name must not be empty",
+            );
+        }
+        if !is_joined_path(&file.file_full_path, &file.directory_path, &file.file_name) {
+            self.push(
+                Severity::Error,
+                DiagnosticCode::FilePathMismatch,
+                format!("{prefix}file_full_path"),
+                format!(
+                    "`{}` is not `{}` joined with `{}`",
+                    file.file_full_path, file.directory_path, file.file_name
+                ),
+            );
+        }
+        self.package_name(&file.package_name, package_name, prefix);
+    }
+
+    /// Warns if a child's package name differs from its parent package.
+    ///
+    /// An empty parent name is not compared, it is reported on its own.
+    fn package_name(&mut self, name: &str, parent: &str, prefix: &str) {
+        if !parent.is_empty() && name != parent {
+            self.push(
+                Severity::Warning,
+                DiagnosticCode::PackageNameMismatch,
+                format!("{prefix}package_name"),
+                format!("`{name}` differs from the parent package name `{parent}`"),
+            );
+        }
+    }
+}
+
+/// Checks whether `full` equals `directory` joined with `name`.
+///
+/// Both `/` and `\` are accepted as separators, and a trailing separator on
+/// `directory` is optional.
+fn is_joined_path(full: &str, directory: &str, name: &str) -> bool {
+    let Some(rest) = full.strip_prefix(directory) else {
+        return false;
+    };
+    if rest == name {
+        return directory.ends_with(['/', '\\']) || directory.is_empty();
+    }
+    rest.strip_prefix(['/', '\\'])
+        .is_some_and(|rest| rest == name)
+}
+
+impl SfdlFile {
+    /// Validate the whole container and return every problem found.
+    ///
+    /// An empty result means the container passed all checks. See the
+    /// [`validation`](crate::validation) module for details.
+    #[must_use]
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator::new(self.encrypted);
+        validator.sfdl_file(self);
+        validator.diagnostics
+    }
+}
+
+impl ConnectionInfo {
+    /// Validate the connection settings, assuming plaintext fields.
+    ///
+    /// Paths in the returned diagnostics are relative to this value.
+    #[must_use]
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator::new(false);
+        validator.connection_info(self, "");
+        validator.diagnostics
+    }
+}
+
+impl SfdlPackage {
+    /// Validate the package and its entries, assuming plaintext fields.
+    ///
+    /// Paths in the returned diagnostics are relative to this value.
+    #[must_use]
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator::new(false);
+        validator.package(self, "");
+        validator.diagnostics
+    }
+}
+
+impl BulkFolder {
+    /// Validate the bulk folder against the name of its parent package.
+    ///
+    /// Paths in the returned diagnostics are relative to this value.
+    #[must_use]
+    pub fn validate(&self, package_name: &str) -> Vec<Diagnostic> {
+        let mut validator = Validator::new(false);
+        validator.bulk_folder(self, package_name, "");
+        validator.diagnostics
+    }
+}
+
+impl FileInfo {
+    /// Validate the file entry against the name of its parent package.
+    ///
+    /// Paths in the returned diagnostics are relative to this value.
+    #[must_use]
+    pub fn validate(&self, package_name: &str) -> Vec<Diagnostic> {
+        let mut validator = Validator::new(false);
+        validator.file_info(self, package_name, "");
+        validator.diagnostics
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use crate::sfdl::{BulkFolderList, FileList, Packages};
+
+    use super::*;
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(DiagnosticCode, &str)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.path.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_files_are_valid() {
+        for name in [
+            "single_package_bulkfolder.xml",
+            "multi_package_bulkfolders.xml",
+            "filelist_mode.xml",
+            "mixed_mode.xml",
+            "minimal_encrypted.xml",
+        ] {
+            let sfdl = SfdlFile::from_file(format!("tests/data/{name}")).unwrap();
+            assert_eq!(sfdl.validate(), vec![], "{name}");
+        }
+    }
+
+    #[test]
+    fn test_connection_info() {
+        let info = ConnectionInfo {
+            port: 0,
+            auth_required: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            codes(&info.validate()),
+            vec![
+                (DiagnosticCode::EmptyHost, "host"),
+                (DiagnosticCode::InvalidPort, "port"),
+                (DiagnosticCode::MissingUsername, "username"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_bulk_folder_list() {
+        let sfdl = SfdlFile {
+            connection_info: ConnectionInfo {
+                host: "host".to_string(),
+                ..Default::default()
+            },
+            packages: Packages {
+                package: vec![
+                    SfdlPackage {
+                        package_name: "Pkg".to_string(),
+                        ..Default::default()
+                    },
+                    SfdlPackage {
+                        package_name: "Files".to_string(),
+                        bulk_folder_mode: false,
+                        ..Default::default()
+                    },
+                ],
+            },
+            ..Default::default()
+        };
+
+        let diagnostics = sfdl.validate();
+        assert_eq!(
+            codes(&diagnostics),
+            vec![
+                (
+                    DiagnosticCode::EmptyBulkFolderList,
+                    "packages[0].bulk_folder_list"
+                ),
+                (DiagnosticCode::EmptyFileList, "packages[1].file_list"),
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_file_info_paths() {
+        let file = FileInfo {
+            file_name: "a.txt".to_string(),
+            directory_path: "/root/dir".to_string(),
+            file_full_path: "/root/dir/a.txt".to_string(),
+            package_name: "Pkg".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(file.validate("Pkg"), vec![]);
+
+        let windows = FileInfo {
+            directory_path: "C:\\dir\\".to_string(),
+            file_full_path: "C:\\dir\\a.txt".to_string(),
+            ..file.clone()
+        };
+        assert_eq!(windows.validate("Pkg"), vec![]);
+
+        let mismatch = FileInfo {
+            file_full_path: "/root/other/a.txt".to_string(),
+            package_name: "Other".to_string(),
+            ..file
+        };
+        assert_eq!(
+            codes(&mismatch.validate("Pkg")),
+            vec![
+                (DiagnosticCode::FilePathMismatch, "file_full_path"),
+                (DiagnosticCode::PackageNameMismatch, "package_name"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let package = SfdlPackage {
+            package_name: "Pkg".to_string(),
+            bulk_folder_mode: false,
+            bulk_folder_list: BulkFolderList {
+                bulk_folder: vec![BulkFolder {
+                    bulk_folder_path: String::new(),
+                    package_name: "Pkg".to_string(),
+                }],
+            },
+            file_list: Some(FileList {
+                file_info: vec![
+                    FileInfo {
+                        file_name: "a".to_string(),
+                        file_full_path: "/a".to_string(),
+                        directory_path: "/".to_string(),
+                        package_name: "Pkg".to_string(),
+                        ..Default::default()
+                    },
+                    FileInfo::default(),
+                ],
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            codes(&package.validate()),
+            vec![
+                (
+                    DiagnosticCode::EmptyBulkFolderPath,
+                    "bulk_folder_list.bulk_folder[0].bulk_folder_path"
+                ),
+                (
+                    DiagnosticCode::EmptyFileName,
+                    "file_list.file_info[1].file_name"
+                ),
+                (
+                    DiagnosticCode::PackageNameMismatch,
+                    "file_list.file_info[1].package_name"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encrypted_fields_are_not_inspected() {
+        let mut sfdl = SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap();
+        sfdl.packages[0].file_list.as_mut().unwrap().file_info[0].file_full_path =
+            "/wrong".to_string();
+        assert_eq!(
+            codes(&sfdl.validate()),
+            vec![(
+                DiagnosticCode::FilePathMismatch,
+                "packages[0].file_list.file_info[0].file_full_path"
+            )]
+        );
+
+        sfdl.encrypt("S3cr3tP4ssw0rd!").unwrap();
+        assert_eq!(sfdl.validate(), vec![]);
+    }
+}
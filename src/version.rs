@@ -0,0 +1,525 @@
+//! Detection and migration of `SFDLFileVersion` layouts.
+//!
+//! The XML layout of an SFDL container depends on its `SFDLFileVersion`.
+//! Containers written by older SFDL.NET releases do not carry the
+//! `ConnectionInfo.DataStaleDetection` and `ConnectionInfo.SpecialServerMode`
+//! elements and usually have no `FileList`. The crate groups versions into
+//! [`VersionFamily`] values and uses a dedicated deserializer for each family.
+//! All families are parsed into the same [`SfdlFile`] model, missing elements
+//! are filled in with the [`ConnectionInfo::default`] values.
+//!
+//! [`SfdlFile::sfdlfile_version`] keeps the version that was read, so a legacy
+//! container is written back in its legacy layout. Legacy containers that
+//! carry `DataStaleDetection` or `SpecialServerMode` anyway keep them, see
+//! [`LegacyElements`]. Use [`SfdlFile::migrate_to`] to convert a container
+//! explicitly.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use sfdl::SfdlFile;
+//! # use sfdl::version::CURRENT_VERSION;
+//! let mut sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+//! sfdl.migrate_to(CURRENT_VERSION).unwrap();
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MigrateError, ParseError};
+use crate::extensions::Extensions;
+use crate::secret::SecretString;
+use crate::sfdl::{
+    CharacterEncoding, ConnectionInfo, DataConnectionType, DataType, EncryptionMode, ListMethod,
+    Packages, SfdlFile,
+};
+
+/// The `SFDLFileVersion` written by current SFDL.NET releases.
+pub const CURRENT_VERSION: u16 = 6;
+
+/// Group of `SFDLFileVersion` values that share the same XML layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VersionFamily {
+    /// Versions below 6.
+    ///
+    /// `DataStaleDetection` and `SpecialServerMode` are optional when reading
+    /// and only written if they were read, see [`LegacyElements`].
+    Legacy,
+    /// Version 6 and newer, the layout of current SFDL.NET releases.
+    Current,
+}
+
+impl VersionFamily {
+    /// Returns the family a `SFDLFileVersion` value belongs to.
+    #[must_use]
+    pub const fn of(version: u16) -> Self {
+        if version < CURRENT_VERSION {
+            Self::Legacy
+        } else {
+            Self::Current
+        }
+    }
+}
+
+/// The elements of the current `ConnectionInfo` layout that a
+/// [`VersionFamily::Legacy`] container carried although its layout does not
+/// define them.
+///
+/// A legacy container is written with exactly these elements. Current
+/// containers always write both, so this is only kept for legacy versions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LegacyElements {
+    /// `DataStaleDetection` was present.
+    pub data_stale_detection: bool,
+    /// `SpecialServerMode` was present.
+    pub special_server_mode: bool,
+}
+
+/// Reads the `SFDLFileVersion` element from an XML document without
+/// deserializing the rest of it.
+///
+/// Returns `None` if the element is missing or not a number.
+///
+/// # Example
+///
+/// ```rust
+/// # use sfdl::version::detect_version;
+/// let xml = "<SFDLFile><SFDLFileVersion>3</SFDLFileVersion></SFDLFile>";
+/// assert_eq!(detect_version(xml), Some(3));
+/// ```
+#[must_use]
+pub fn detect_version(xml: &str) -> Option<u16> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut depth = 0usize;
+    let mut in_version = false;
+
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) => {
+                depth += 1;
+                in_version = depth == 2 && start.local_name().as_ref() == b"SFDLFileVersion";
+            }
+            Event::End(_) => {
+                if in_version {
+                    return None;
+                }
+                depth = depth.checked_sub(1)?;
+            }
+            Event::Text(text) if in_version => {
+                return text.decode().ok()?.trim().parse().ok();
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Parses an XML document with the deserializer of its version family.
+pub(crate) fn from_str(xml: &str) -> Result<SfdlFile, ParseError> {
+    let family = detect_version(xml).map_or(VersionFamily::Current, VersionFamily::of);
+
+    match family {
+        VersionFamily::Legacy => quick_xml::de::from_str::<LegacySfdlFile>(xml)
+            .map(SfdlFile::from)
+            .map_err(ParseError::InvalidSfdlDeserialize),
+        VersionFamily::Current => {
+            quick_xml::de::from_str(xml).map_err(ParseError::InvalidSfdlDeserialize)
+        }
+    }
+}
+
+/// Parses a `ConnectionInfo` element with the layout of `version`.
+pub(crate) fn connection_info_from_str(
+    xml: &str,
+    version: u16,
+) -> Result<ConnectionInfo, quick_xml::DeError> {
+    match VersionFamily::of(version) {
+        VersionFamily::Legacy => {
+            quick_xml::de::from_str::<LegacyConnectionInfo>(xml).map(ConnectionInfo::from)
+        }
+        VersionFamily::Current => quick_xml::de::from_str(xml),
+    }
+}
+
+/// Serializes a container with the layout of its version family.
+///
+/// Fails like [`SfdlFile::migrate_to`] if a legacy container holds a value
+/// that its layout cannot store.
+pub(crate) fn to_writer<W: std::fmt::Write>(sfdl: &SfdlFile, writer: W) -> Result<(), ParseError> {
+    match VersionFamily::of(sfdl.sfdlfile_version) {
+        VersionFamily::Legacy => {
+            check_representable(&sfdl.connection_info, sfdl.sfdlfile_version).map_err(|err| {
+                ParseError::InvalidSfdlSerialize(quick_xml::SeError::Custom(err.to_string()))
+            })?;
+            quick_xml::se::to_writer(writer, &LegacyView::from(sfdl))
+        }
+        VersionFamily::Current => quick_xml::se::to_writer(writer, sfdl),
+    }
+    .map_err(ParseError::InvalidSfdlSerialize)?;
+    Ok(())
+}
+
+/// Checks that the legacy layout of `version` can store `info`: values that
+/// differ from their default need their element, which legacy layouts only
+/// have if the container was read with it.
+fn check_representable(info: &ConnectionInfo, version: u16) -> Result<(), MigrateError> {
+    let defaults = ConnectionInfo::default();
+    let present = info.legacy_elements;
+    if info.data_stale_detection != defaults.data_stale_detection && !present.data_stale_detection {
+        return Err(MigrateError::Unrepresentable {
+            field: "connection_info.data_stale_detection",
+            version,
+        });
+    }
+    if info.special_server_mode != defaults.special_server_mode && !present.special_server_mode {
+        return Err(MigrateError::Unrepresentable {
+            field: "connection_info.special_server_mode",
+            version,
+        });
+    }
+    Ok(())
+}
+
+impl SfdlFile {
+    /// Change [`SfdlFile::sfdlfile_version`] and thereby the layout used when
+    /// writing the container.
+    ///
+    /// Upgrading a legacy container to the current version keeps the values
+    /// that were filled in for missing elements. Downgrading to a legacy
+    /// version is refused if a value that the legacy layout cannot store
+    /// differs from its default, unless the container was read with that
+    /// element, see [`LegacyElements`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MigrateError::UnsupportedVersion`] for version `0` or versions
+    /// newer than [`CURRENT_VERSION`], or [`MigrateError::Unrepresentable`] if
+    /// a downgrade would lose data.
+    pub fn migrate_to(&mut self, version: u16) -> Result<(), MigrateError> {
+        if version == 0 || version > CURRENT_VERSION {
+            return Err(MigrateError::UnsupportedVersion(version));
+        }
+
+        match VersionFamily::of(version) {
+            VersionFamily::Legacy => check_representable(&self.connection_info, version)?,
+            VersionFamily::Current => {
+                self.connection_info.legacy_elements = LegacyElements::default()
+            }
+        }
+
+        self.sfdlfile_version = version;
+        Ok(())
+    }
+}
+
+/// `SFDLFile` layout of the [`VersionFamily::Legacy`] family.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase", rename = "SFDLFile")]
+struct LegacySfdlFile {
+    #[serde(rename = "@xmlns:xsd", default = "crate::sfdl::default_xmlns_xsd")]
+    xmlns_xsd: String,
+    #[serde(rename = "@xmlns:xsi", default = "crate::sfdl::default_xmlns_xsi")]
+    xmlns_xsi: String,
+    description: String,
+    uploader: String,
+    #[serde(rename = "SFDLFileVersion")]
+    sfdlfile_version: u16,
+    encrypted: bool,
+    connection_info: LegacyConnectionInfo,
+    packages: Packages,
+    max_download_threads: u16,
+}
+
+/// `ConnectionInfo` layout of the [`VersionFamily::Legacy`] family.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LegacyConnectionInfo {
+    host: String,
+    port: u16,
+    username: SecretString,
+    password: SecretString,
+    auth_required: bool,
+    data_connection_type: DataConnectionType,
+    data_type: DataType,
+    character_encoding: CharacterEncoding,
+    encryption_mode: EncryptionMode,
+    list_method: ListMethod,
+    default_path: String,
+    force_single_connection: bool,
+    #[serde(default)]
+    data_stale_detection: Option<bool>,
+    #[serde(default)]
+    special_server_mode: Option<bool>,
+}
+
+/// [`LegacySfdlFile`] layout borrowing the container it serializes.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase", rename = "SFDLFile")]
+struct LegacyView<'a> {
+    #[serde(rename = "@xmlns:xsd")]
+    xmlns_xsd: &'a str,
+    #[serde(rename = "@xmlns:xsi")]
+    xmlns_xsi: &'a str,
+    description: &'a str,
+    uploader: &'a str,
+    #[serde(rename = "SFDLFileVersion")]
+    sfdlfile_version: u16,
+    encrypted: bool,
+    connection_info: LegacyConnectionView<'a>,
+    packages: &'a Packages,
+    max_download_threads: u16,
+}
+
+/// [`LegacyConnectionInfo`] layout borrowing the connection it serializes.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct LegacyConnectionView<'a> {
+    host: &'a str,
+    port: u16,
+    username: &'a SecretString,
+    password: &'a SecretString,
+    auth_required: bool,
+    data_connection_type: &'a DataConnectionType,
+    data_type: &'a DataType,
+    character_encoding: &'a CharacterEncoding,
+    encryption_mode: &'a EncryptionMode,
+    list_method: &'a ListMethod,
+    default_path: &'a str,
+    force_single_connection: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_stale_detection: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    special_server_mode: Option<bool>,
+}
+
+impl From<LegacySfdlFile> for SfdlFile {
+    fn from(legacy: LegacySfdlFile) -> Self {
+        Self {
+            xmlns_xsd: legacy.xmlns_xsd,
+            xmlns_xsi: legacy.xmlns_xsi,
+            description: legacy.description,
+            uploader: legacy.uploader,
+            sfdlfile_version: legacy.sfdlfile_version,
+            encrypted: legacy.encrypted,
+            connection_info: legacy.connection_info.into(),
+            packages: legacy.packages,
+            max_download_threads: legacy.max_download_threads,
+            extensions: Extensions::default(),
+        }
+    }
+}
+
+impl From<LegacyConnectionInfo> for ConnectionInfo {
+    fn from(info: LegacyConnectionInfo) -> Self {
+        let defaults = Self::default();
+        let legacy_elements = LegacyElements {
+            data_stale_detection: info.data_stale_detection.is_some(),
+            special_server_mode: info.special_server_mode.is_some(),
+        };
+
+        Self {
+            host: info.host,
+            port: info.port,
+            username: info.username,
+            password: info.password,
+            auth_required: info.auth_required,
+            data_connection_type: info.data_connection_type,
+            data_type: info.data_type,
+            character_encoding: info.character_encoding,
+            encryption_mode: info.encryption_mode,
+            list_method: info.list_method,
+            default_path: info.default_path,
+            force_single_connection: info.force_single_connection,
+            data_stale_detection: info
+                .data_stale_detection
+                .unwrap_or(defaults.data_stale_detection),
+            special_server_mode: info
+                .special_server_mode
+                .unwrap_or(defaults.special_server_mode),
+            legacy_elements,
+            extensions: Extensions::default(),
+        }
+    }
+}
+
+impl<'a> From<&'a SfdlFile> for LegacyView<'a> {
+    fn from(sfdl: &'a SfdlFile) -> Self {
+        let info = &sfdl.connection_info;
+        let present = info.legacy_elements;
+
+        Self {
+            xmlns_xsd: &sfdl.xmlns_xsd,
+            xmlns_xsi: &sfdl.xmlns_xsi,
+            description: &sfdl.description,
+            uploader: &sfdl.uploader,
+            sfdlfile_version: sfdl.sfdlfile_version,
+            encrypted: sfdl.encrypted,
+            connection_info: LegacyConnectionView {
+                host: &info.host,
+                port: info.port,
+                username: &info.username,
+                password: &info.password,
+                auth_required: info.auth_required,
+                data_connection_type: &info.data_connection_type,
+                data_type: &info.data_type,
+                character_encoding: &info.character_encoding,
+                encryption_mode: &info.encryption_mode,
+                list_method: &info.list_method,
+                default_path: &info.default_path,
+                force_single_connection: info.force_single_connection,
+                data_stale_detection: present
+                    .data_stale_detection
+                    .then_some(info.data_stale_detection),
+                special_server_mode: present
+                    .special_server_mode
+                    .then_some(info.special_server_mode),
+            },
+            packages: &sfdl.packages,
+            max_download_threads: sfdl.max_download_threads,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn legacy_xml() -> String {
+        std::fs::read_to_string("tests/data/legacy_v3.xml").unwrap()
+    }
+
+    #[test]
+    fn test_detect_version() {
+        assert_eq!(detect_version(&legacy_xml()), Some(3));
+        assert_eq!(
+            detect_version(&std::fs::read_to_string("tests/data/mixed_mode.xml").unwrap()),
+            Some(6)
+        );
+        assert_eq!(detect_version("<SFDLFile></SFDLFile>"), None);
+        assert_eq!(
+            detect_version("<SFDLFile><SFDLFileVersion>x</SFDLFileVersion></SFDLFile>"),
+            None
+        );
+        // Nested elements with the same name are not the file version.
+        assert_eq!(
+            detect_version("<SFDLFile><A><SFDLFileVersion>2</SFDLFileVersion></A></SFDLFile>"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy() {
+        let sfdl: SfdlFile = legacy_xml().parse().unwrap();
+
+        assert_eq!(sfdl.sfdlfile_version, 3);
+        assert!(sfdl.connection_info.data_stale_detection);
+        assert!(!sfdl.connection_info.special_server_mode);
+        assert_eq!(sfdl.packages[0].package_name, "LegacyPackage");
+        assert_eq!(sfdl.packages[0].file_list, None);
+    }
+
+    #[test]
+    fn test_legacy_layout_is_kept_on_write() {
+        let sfdl: SfdlFile = legacy_xml().parse().unwrap();
+        let xml = sfdl.to_xml_string().unwrap();
+
+        assert!(!xml.contains("DataStaleDetection"));
+        assert!(!xml.contains("SpecialServerMode"));
+        assert_eq!(xml.parse::<SfdlFile>().unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_legacy_elements_round_trip() {
+        let xml = std::fs::read_to_string("tests/data/legacy_v5_with_elements.xml").unwrap();
+        let sfdl: SfdlFile = xml.parse().unwrap();
+
+        assert_eq!(sfdl.sfdlfile_version, 5);
+        assert!(sfdl.connection_info.data_stale_detection);
+        assert!(sfdl.connection_info.special_server_mode);
+        assert_eq!(
+            sfdl.connection_info.legacy_elements,
+            LegacyElements {
+                data_stale_detection: true,
+                special_server_mode: true,
+            }
+        );
+
+        let written = sfdl.to_xml_string().unwrap();
+        assert!(written.contains(
+            "<ForceSingleConnection>false</ForceSingleConnection>\
+             <DataStaleDetection>true</DataStaleDetection>\
+             <SpecialServerMode>true</SpecialServerMode></ConnectionInfo>"
+        ));
+        assert_eq!(written.parse::<SfdlFile>().unwrap(), sfdl);
+
+        // The elements were read, so the legacy layout can store them.
+        let mut migrated = sfdl.clone();
+        migrated.migrate_to(3).unwrap();
+        migrated.migrate_to(CURRENT_VERSION).unwrap();
+        assert_eq!(
+            migrated.connection_info.legacy_elements,
+            LegacyElements::default()
+        );
+        assert!(migrated.connection_info.special_server_mode);
+    }
+
+    #[test]
+    fn test_unrepresentable_legacy_value_is_not_written() {
+        let mut sfdl: SfdlFile = legacy_xml().parse().unwrap();
+        sfdl.connection_info.special_server_mode = true;
+
+        let err = sfdl.to_xml_string().unwrap_err();
+        let ParseError::InvalidSfdlSerialize(quick_xml::SeError::Custom(message)) = err else {
+            panic!("{err:?}");
+        };
+        assert_eq!(
+            message,
+            MigrateError::Unrepresentable {
+                field: "connection_info.special_server_mode",
+                version: 3,
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_migrate_to_current() {
+        let mut sfdl: SfdlFile = legacy_xml().parse().unwrap();
+        sfdl.migrate_to(CURRENT_VERSION).unwrap();
+
+        let xml = sfdl.to_xml_string().unwrap();
+        assert!(xml.contains("<DataStaleDetection>true</DataStaleDetection>"));
+        assert!(xml.contains("<SpecialServerMode>false</SpecialServerMode>"));
+        assert_eq!(xml.parse::<SfdlFile>().unwrap(), sfdl);
+    }
+
+    #[test]
+    fn test_migrate_errors() {
+        let mut sfdl = SfdlFile::from_file("tests/data/mixed_mode.xml").unwrap();
+
+        assert_eq!(sfdl.migrate_to(0), Err(MigrateError::UnsupportedVersion(0)));
+        assert_eq!(
+            sfdl.migrate_to(CURRENT_VERSION + 1),
+            Err(MigrateError::UnsupportedVersion(CURRENT_VERSION + 1))
+        );
+
+        sfdl.connection_info.special_server_mode = true;
+        assert_eq!(
+            sfdl.migrate_to(3),
+            Err(MigrateError::Unrepresentable {
+                field: "connection_info.special_server_mode",
+                version: 3,
+            })
+        );
+        assert_eq!(sfdl.sfdlfile_version, CURRENT_VERSION);
+
+        sfdl.connection_info.special_server_mode = false;
+        sfdl.migrate_to(3).unwrap();
+        assert_eq!(sfdl.sfdlfile_version, 3);
+    }
+}
@@ -0,0 +1,611 @@
+//! Output formatting for serialized SFDL containers, and crash-safe writing
+//! of files.
+//!
+//! By default [`SfdlFile::to_xml_string`](crate::SfdlFile::to_xml_string)
+//! writes the whole container on a single line without an XML declaration.
+//! [`WriteOptions`] controls the declaration, indentation, line endings and a
+//! UTF-8 byte order mark, and [`WriteOptions::dotnet`] reproduces the output
+//! of the .NET `XmlSerializer` that SFDL.NET uses.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use sfdl::{SfdlFile, WriteOptions};
+//! let sfdl = SfdlFile::from_file("examples/decrypted.sfdl").unwrap();
+//!
+//! let options = WriteOptions::new().declaration(true).indent("  ");
+//! let xml = sfdl.to_xml_string_with(&options).unwrap();
+//!
+//! assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<SFDLFile"));
+//! assert!(xml.contains("\n  <Description>MyDescription</Description>\n"));
+//! ```
+//!
+//! # Atomic writes
+//!
+//! [`SfdlFile::write`](crate::SfdlFile::write) never truncates the target. It
+//! writes a temporary file next to it, flushes it to disk and renames it over
+//! the target, so a crash leaves either the old or the new container. With
+//! [`WriteOptions::backup`] the previous file is kept as `<name>.bak`.
+//! [`write_atomic`] does the same for arbitrary content, such as the JSON
+//! form of a container.
+
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use encoding_rs::{Encoding, UTF_8};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::encoding::{self, SourceEncoding};
+use crate::error::ParseError;
+use crate::extensions::se_error;
+
+/// The line ending written between elements when indenting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`, as written by .NET on Windows.
+    CrLf,
+}
+
+impl LineEnding {
+    /// The characters of this line ending.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Formatting options for writing SFDL containers.
+///
+/// The default writes compact UTF-8 output, exactly as
+/// [`SfdlFile::to_xml_string`](crate::SfdlFile::to_xml_string) does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct WriteOptions {
+    /// Write an `<?xml version="1.0" encoding="..."?>` declaration.
+    pub declaration: bool,
+    /// The encoding named in the declaration and used by
+    /// [`SfdlFile::to_xml_bytes_with`](crate::SfdlFile::to_xml_bytes_with).
+    /// Characters it cannot represent are written as numeric character
+    /// references.
+    pub encoding: &'static Encoding,
+    /// Indentation for one nesting level. `None` writes everything on a single
+    /// line.
+    pub indent: Option<String>,
+    /// The line ending used between indented elements and after the
+    /// declaration. Line breaks inside text content are left untouched
+    /// unless [`normalize_newlines`](Self::normalize_newlines) is set.
+    pub line_ending: LineEnding,
+    /// Replace every line break inside text and attribute values with
+    /// [`line_ending`](Self::line_ending), like .NET's
+    /// `NewLineHandling.Replace`. In attribute values the line ending is
+    /// written as character references, so it survives parsing.
+    pub normalize_newlines: bool,
+    /// Prefix the output with a byte order mark. Only UTF-8 and UTF-16 have
+    /// one; for other encodings this is ignored.
+    pub bom: bool,
+    /// Write empty elements as `<Name />` instead of `<Name/>`.
+    pub space_before_self_close: bool,
+    /// Keep the file being replaced as `<name>.bak`. Only used when writing
+    /// to a path.
+    pub backup: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            declaration: false,
+            encoding: UTF_8,
+            indent: None,
+            line_ending: LineEnding::Lf,
+            normalize_newlines: false,
+            bom: false,
+            space_before_self_close: false,
+            backup: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Compact UTF-8 output without declaration, indentation or BOM.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The output of the .NET `XmlSerializer` as used by SFDL.NET, byte for
+    /// byte: a UTF-8 BOM, the XML declaration, two-space indentation, `\r\n`
+    /// line endings, also inside text, empty elements written as `<Name />`
+    /// and no trailing line break.
+    pub fn dotnet() -> Self {
+        Self {
+            declaration: true,
+            encoding: UTF_8,
+            indent: Some("  ".to_string()),
+            line_ending: LineEnding::CrLf,
+            normalize_newlines: true,
+            bom: true,
+            space_before_self_close: true,
+            backup: false,
+        }
+    }
+
+    /// Set whether the XML declaration is written.
+    pub fn declaration(mut self, declaration: bool) -> Self {
+        self.declaration = declaration;
+        self
+    }
+
+    /// Set the output encoding.
+    pub fn encoding(mut self, encoding: &'static Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Write in the encoding, and with the byte order mark, a container was
+    /// read in.
+    pub fn source_encoding(mut self, source: SourceEncoding) -> Self {
+        self.encoding = source.encoding;
+        self.bom = source.bom;
+        self
+    }
+
+    /// Indent nested elements by `indent` per level.
+    pub fn indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = Some(indent.into());
+        self
+    }
+
+    /// Set the line ending.
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Set whether line breaks inside text and attribute values are replaced
+    /// with the line ending.
+    pub fn normalize_newlines(mut self, normalize: bool) -> Self {
+        self.normalize_newlines = normalize;
+        self
+    }
+
+    /// Set whether a UTF-8 byte order mark is written.
+    pub fn bom(mut self, bom: bool) -> Self {
+        self.bom = bom;
+        self
+    }
+
+    /// Set whether empty elements are written as `<Name />`.
+    pub fn space_before_self_close(mut self, space: bool) -> Self {
+        self.space_before_self_close = space;
+        self
+    }
+
+    /// Set whether the replaced file is kept as `<name>.bak`.
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    fn is_compact(&self) -> bool {
+        self.indent.is_none() && !self.space_before_self_close && !self.normalize_newlines
+    }
+}
+
+/// Writes the compact serializer output `xml` formatted according to
+/// `options` into `out`. The byte order mark is left to the caller.
+pub(crate) fn format(
+    xml: &str,
+    options: &WriteOptions,
+    out: &mut String,
+) -> Result<(), ParseError> {
+    if options.declaration {
+        out.push_str(r#"<?xml version="1.0" encoding=""#);
+        out.push_str(&encoding::declaration_label(options.encoding));
+        out.push_str(r#""?>"#);
+        if options.indent.is_some() {
+            out.push_str(options.line_ending.as_str());
+        }
+    }
+    if options.is_compact() {
+        out.push_str(xml);
+        return Ok(());
+    }
+
+    let mut formatter = Formatter {
+        options,
+        out,
+        depth: 0,
+        text: String::new(),
+        open: false,
+        started: false,
+    };
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event().map_err(se_error)? {
+            Event::Start(element) => {
+                formatter.markup(&["<", &formatter.tag(&element)?, ">"]);
+                formatter.depth += 1;
+                formatter.open = true;
+            }
+            Event::Empty(element) => {
+                let content = formatter.tag(&element)?;
+                if options.space_before_self_close {
+                    formatter.markup(&["<", content.trim_end(), " />"]);
+                } else {
+                    formatter.markup(&["<", &content, "/>"]);
+                }
+            }
+            Event::End(element) => {
+                formatter.depth = formatter.depth.saturating_sub(1);
+                if formatter.open {
+                    // A leaf element keeps its text, whitespace included.
+                    let text = std::mem::take(&mut formatter.text);
+                    formatter.out.push_str(&text);
+                    formatter.out.push_str("</");
+                    formatter.out.push_str(&utf8(&element)?);
+                    formatter.out.push('>');
+                    formatter.open = false;
+                } else {
+                    formatter.markup(&["</", &utf8(&element)?, ">"]);
+                }
+            }
+            Event::Text(text) => {
+                let text = formatter.newlines(utf8(&text)?);
+                formatter.text.push_str(&text);
+            }
+            Event::GeneralRef(reference) => {
+                formatter.text.push('&');
+                formatter.text.push_str(&utf8(&reference)?);
+                formatter.text.push(';');
+            }
+            Event::CData(cdata) => {
+                let cdata = formatter.newlines(utf8(&cdata)?);
+                formatter.markup(&["<![CDATA[", &cdata, "]]>"]);
+            }
+            Event::Comment(comment) => {
+                let comment = formatter.newlines(utf8(&comment)?);
+                formatter.markup(&["<!--", &comment, "-->"]);
+            }
+            Event::PI(pi) => formatter.markup(&["<?", &utf8(&pi)?, "?>"]),
+            Event::Eof => break,
+            Event::Decl(_) | Event::DocType(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+struct Formatter<'a> {
+    options: &'a WriteOptions,
+    out: &'a mut String,
+    depth: usize,
+    /// Text read since the last markup, written once it is known whether it
+    /// is element content or whitespace between elements.
+    text: String,
+    /// Whether the last markup was a start tag.
+    open: bool,
+    /// Whether any markup has been written yet.
+    started: bool,
+}
+
+impl Formatter<'_> {
+    /// The name and attributes of a tag, with line breaks inside attribute
+    /// values replaced if requested.
+    fn tag<'b>(&self, element: &'b [u8]) -> Result<Cow<'b, str>, ParseError> {
+        let tag = utf8(element)?;
+        if !self.options.normalize_newlines || !tag.contains(['\r', '\n']) {
+            return Ok(tag);
+        }
+
+        let reference = match self.options.line_ending {
+            LineEnding::Lf => "&#xA;",
+            LineEnding::CrLf => "&#xD;&#xA;",
+        };
+        let mut out = String::with_capacity(tag.len());
+        let mut quote = None;
+        let mut chars = tag.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(open), _) if c == open => quote = None,
+                (Some(_), '\r' | '\n') => {
+                    if c == '\r' {
+                        chars.next_if_eq(&'\n');
+                    }
+                    out.push_str(reference);
+                    continue;
+                }
+                _ => {}
+            }
+            out.push(c);
+        }
+        Ok(Cow::Owned(out))
+    }
+
+    /// `text` with every `\r\n`, `\r` and `\n` replaced by the line ending,
+    /// if requested.
+    fn newlines<'b>(&self, text: Cow<'b, str>) -> Cow<'b, str> {
+        if !self.options.normalize_newlines || !text.contains(['\r', '\n']) {
+            return text;
+        }
+
+        let line_ending = self.options.line_ending.as_str();
+        let mut out = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => {
+                    chars.next_if_eq(&'\n');
+                    out.push_str(line_ending);
+                }
+                '\n' => out.push_str(line_ending),
+                c => out.push(c),
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    /// Writes markup on its own line, dropping whitespace-only text before it.
+    fn markup(&mut self, parts: &[&str]) {
+        let text = std::mem::take(&mut self.text);
+        if self.options.indent.is_none() || !text.trim().is_empty() {
+            self.out.push_str(&text);
+        }
+        if let Some(indent) = &self.options.indent {
+            if self.started {
+                self.out.push_str(self.options.line_ending.as_str());
+            }
+            for _ in 0..self.depth {
+                self.out.push_str(indent);
+            }
+        }
+        for part in parts {
+            self.out.push_str(part);
+        }
+        self.started = true;
+        self.open = false;
+    }
+}
+
+/// Replaces `path` with `content` without ever leaving a partially written
+/// file behind, optionally copying the previous file to `<name>.bak` first.
+///
+/// # Errors
+///
+/// Returns the I/O error of writing the temporary file, copying the backup
+/// or renaming. The target is left unchanged in that case.
+pub fn write_atomic(path: &Path, content: &[u8], backup: bool) -> io::Result<()> {
+    let temp = temp_path(path);
+    let result = write_temp(&temp, path, content).and_then(|()| {
+        if backup {
+            copy_to_backup(path)?;
+        }
+        fs::rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+
+    sync_parent(path);
+    Ok(())
+}
+
+fn write_temp(temp: &Path, path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create_new(temp)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+fn copy_to_backup(path: &Path) -> io::Result<()> {
+    match fs::copy(path, backup_path(path)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Makes the rename durable. Only possible, and needed, on Unix.
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        let _ = fs::File::open(parent).and_then(|dir| dir.sync_all());
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// A unique hidden file next to `path`, like `.name.sfdl.1234.0.tmp`.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// `path` with `.bak` appended to its file name.
+pub(crate) fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+fn utf8(bytes: &[u8]) -> Result<Cow<'_, str>, ParseError> {
+    std::str::from_utf8(bytes)
+        .map(Cow::Borrowed)
+        .map_err(|err| ParseError::InvalidSfdlSerialize(err.into()))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::SfdlFile;
+
+    fn sample() -> SfdlFile {
+        SfdlFile::from_file("tests/data/single_package_bulkfolder.xml").unwrap()
+    }
+
+    #[test]
+    fn test_default_options_match_to_xml_string() {
+        let sfdl = sample();
+        assert_eq!(
+            sfdl.to_xml_string_with(&WriteOptions::default()).unwrap(),
+            sfdl.to_xml_string().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dotnet_preset() {
+        let mut sfdl = sample();
+        sfdl.description = "a < b\n & c".to_string();
+        sfdl.uploader = String::new();
+
+        let xml = sfdl.to_xml_string_with(&WriteOptions::dotnet()).unwrap();
+        let expected = [
+            "\u{FEFF}<?xml version=\"1.0\" encoding=\"utf-8\"?>",
+            "<SFDLFile xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">",
+            "  <Description>a &lt; b\r\n &amp; c</Description>",
+            "  <Uploader />",
+            "  <SFDLFileVersion>6</SFDLFileVersion>",
+            "  <Encrypted>false</Encrypted>",
+            "  <ConnectionInfo>",
+        ]
+        .join("\r\n");
+        assert!(xml.starts_with(&expected), "{xml}");
+        assert!(xml.contains("\r\n  <Packages>\r\n    <SFDLPackage>\r\n      <Packagename>"));
+        assert!(xml.ends_with("</MaxDownloadThreads>\r\n</SFDLFile>"));
+
+        let reparsed: SfdlFile = xml.parse().unwrap();
+        assert_eq!(reparsed, sfdl);
+    }
+
+    /// `dotnet_reference.sfdl` is laid out the way SFDL.NET saves a
+    /// container, with a UTF-8 BOM, `\r\n` everywhere and a description that
+    /// spans two lines.
+    #[test]
+    fn test_dotnet_matches_reference() {
+        let reference = fs::read("tests/data/dotnet_reference.sfdl").unwrap();
+        let (sfdl, _) = SfdlFile::from_bytes(&reference).unwrap();
+        assert_eq!(sfdl.description, "Season pack\nEpisodes 1 & 2");
+
+        let written = sfdl.to_xml_bytes_with(&WriteOptions::dotnet()).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            String::from_utf8(reference).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_newlines_are_normalized() {
+        let mut sfdl = sample();
+        sfdl.description = "a\r\nb\rc\nd".to_string();
+        sfdl.extensions
+            .attributes
+            .push(("Note".to_string(), "x\ny".to_string()));
+
+        let options = WriteOptions::new().normalize_newlines(true);
+        let xml = sfdl.to_xml_string_with(&options).unwrap();
+        assert!(
+            xml.contains("<Description>a\nb\nc\nd</Description>"),
+            "{xml}"
+        );
+        assert!(xml.contains(" Note=\"x&#xA;y\">"), "{xml}");
+
+        let xml = sfdl
+            .to_xml_string_with(&options.line_ending(LineEnding::CrLf))
+            .unwrap();
+        assert!(xml.contains("<Description>a\r\nb\r\nc\r\nd</Description>"));
+        assert!(xml.contains(" Note=\"x&#xD;&#xA;y\">"), "{xml}");
+
+        let reparsed: SfdlFile = xml.parse().unwrap();
+        assert_eq!(
+            reparsed.extensions.attributes,
+            [("Note".to_string(), "x\r\ny".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_whitespace_text_is_kept() {
+        let mut sfdl = sample();
+        sfdl.description = "  ".to_string();
+
+        let xml = sfdl
+            .to_xml_string_with(&WriteOptions::new().indent("\t"))
+            .unwrap();
+        assert!(xml.contains("\n\t<Description>  </Description>\n"), "{xml}");
+        assert!(!xml.starts_with('\n'));
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir().join(format!("sfdl-writer-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.sfdl");
+
+        write_atomic(&path, b"first", true).unwrap();
+        assert!(!backup_path(&path).exists());
+        write_atomic(&path, b"second", false).unwrap();
+        write_atomic(&path, b"third", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"third");
+        assert_eq!(fs::read(backup_path(&path)).unwrap(), b"second");
+
+        // Only the target and its backup are left, no temporary files.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_target_on_error() {
+        let path = std::env::temp_dir().join(format!("sfdl-missing-{}", process::id()));
+        let target = path.join("out.sfdl");
+
+        assert!(write_atomic(&target, b"data", false).is_err());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_extensions_are_indented() {
+        let xml = std::fs::read_to_string("tests/data/mixed_mode.xml")
+            .unwrap()
+            .replace(
+                "</ConnectionInfo>",
+                "<Proxy kind=\"socks\"><Host>p</Host></Proxy></ConnectionInfo>",
+            );
+        let sfdl: SfdlFile = xml.parse().unwrap();
+
+        let xml = sfdl
+            .to_xml_string_with(&WriteOptions::new().indent("  "))
+            .unwrap();
+        assert!(
+            xml.contains(
+                "\n    <Proxy kind=\"socks\">\n      <Host>p</Host>\n    </Proxy>\n  </ConnectionInfo>"
+            ),
+            "{xml}"
+        );
+    }
+}
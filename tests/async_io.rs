@@ -0,0 +1,55 @@
+#![cfg(feature = "tokio")]
+
+use sfdl::{SfdlFile, WriteOptions};
+
+#[tokio::test]
+async fn async_reader_matches_from_file() {
+    let expected = SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap();
+
+    let file = tokio::fs::File::open("tests/data/filelist_mode.xml")
+        .await
+        .unwrap();
+    let sfdl = SfdlFile::from_async_reader(file).await.unwrap();
+    assert_eq!(sfdl, expected);
+
+    let sfdl = SfdlFile::from_file_async("tests/data/filelist_mode.xml")
+        .await
+        .unwrap();
+    assert_eq!(sfdl, expected);
+}
+
+#[tokio::test]
+async fn async_writer_matches_sync_bytes() {
+    let sfdl = SfdlFile::from_file("tests/data/mixed_mode.xml").unwrap();
+    let options = WriteOptions::dotnet();
+
+    let mut bytes = Vec::new();
+    sfdl.to_async_writer_with(&mut bytes, &options)
+        .await
+        .unwrap();
+    assert_eq!(bytes, sfdl.to_xml_bytes_with(&options).unwrap());
+
+    let mut compact = Vec::new();
+    sfdl.to_async_writer(&mut compact).await.unwrap();
+    assert_eq!(compact, sfdl.to_xml_string().unwrap().into_bytes());
+}
+
+#[tokio::test]
+async fn write_async_round_trips() {
+    let sfdl = SfdlFile::from_file("tests/data/mixed_mode.xml").unwrap();
+    let path = std::env::temp_dir().join(format!("sfdl-async-{}.sfdl", std::process::id()));
+
+    sfdl.write_async(&path).await.unwrap();
+    assert_eq!(SfdlFile::from_file_async(&path).await.unwrap(), sfdl);
+
+    let before = tokio::fs::read(&path).await.unwrap();
+    let options = WriteOptions::dotnet().backup(true);
+    sfdl.write_async_with(&path, &options).await.unwrap();
+    let mut backup = path.clone().into_os_string();
+    backup.push(".bak");
+    assert_eq!(tokio::fs::read(&backup).await.unwrap(), before);
+    assert_eq!(SfdlFile::from_file_async(&path).await.unwrap(), sfdl);
+
+    tokio::fs::remove_file(&path).await.unwrap();
+    tokio::fs::remove_file(&backup).await.unwrap();
+}
@@ -0,0 +1,252 @@
+#![cfg(feature = "cli")]
+#![allow(clippy::unwrap_used)]
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use sfdl::SfdlFile;
+
+const PASSWORD: &str = "S3cr3tP4ssw0rd!";
+
+fn sfdl(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sfdl"))
+        .args(args)
+        .env_remove("SFDL_PASSWORD")
+        .env_remove("SFDL_OLD_PASSWORD")
+        .env_remove("SFDL_NEW_PASSWORD")
+        .output()
+        .unwrap()
+}
+
+fn temp_copy(sample: &str, name: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("cli-{name}.sfdl"));
+    std::fs::copy(format!("tests/data/{sample}"), &path).unwrap();
+    path
+}
+
+#[test]
+fn info_prints_summary() {
+    let output = sfdl(&["info", "tests/data/mixed_mode.xml"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Mixed mode description"));
+    assert!(stdout.contains("Packages:     2"));
+    assert!(stdout.contains("Host:         [redacted]:"));
+
+    let output = sfdl(&["info", "--show-secrets", "tests/data/mixed_mode.xml"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("[redacted]"));
+}
+
+#[test]
+fn encrypt_then_decrypt_restores_container() {
+    let path = temp_copy("filelist_mode.xml", "encrypt-decrypt");
+    let path_str = path.to_str().unwrap();
+
+    let output = sfdl(&["encrypt", "-p", PASSWORD, path_str]);
+    assert!(output.status.success());
+    assert!(SfdlFile::from_file(&path).unwrap().encrypted);
+
+    let output = sfdl(&["decrypt", "-p", PASSWORD, path_str]);
+    assert!(output.status.success());
+    assert_eq!(
+        SfdlFile::from_file(&path).unwrap(),
+        SfdlFile::from_file("tests/data/filelist_mode.xml").unwrap()
+    );
+}
+
+#[test]
+fn password_is_read_from_piped_stdin() {
+    let path = temp_copy("minimal_encrypted.xml", "stdin-password");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sfdl"))
+        .args(["decrypt", path.to_str().unwrap()])
+        .env_remove("SFDL_PASSWORD")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "{PASSWORD}").unwrap();
+    drop(stdin);
+
+    assert!(child.wait().unwrap().success());
+    assert!(!SfdlFile::from_file(&path).unwrap().encrypted);
+}
+
+#[test]
+fn rekey_changes_password() {
+    let path = temp_copy("minimal_encrypted.xml", "rekey");
+    let path_str = path.to_str().unwrap();
+
+    let output = sfdl(&[
+        "rekey",
+        "--old-password",
+        PASSWORD,
+        "--new-password",
+        "n3wP4ssw0rd",
+        path_str,
+    ]);
+    assert!(output.status.success());
+
+    let mut sfdl = SfdlFile::from_file(&path).unwrap();
+    sfdl.decrypt("n3wP4ssw0rd").unwrap();
+    assert_eq!(
+        sfdl,
+        SfdlFile::from_file("tests/data/single_package_bulkfolder.xml").unwrap()
+    );
+}
+
+#[test]
+fn backup_keeps_replaced_file() {
+    let path = temp_copy("filelist_mode.xml", "backup");
+    let path_str = path.to_str().unwrap();
+    let original = std::fs::read(&path).unwrap();
+
+    let output = sfdl(&["encrypt", "-p", PASSWORD, "--backup", path_str]);
+    assert!(output.status.success());
+
+    let mut backup = path.into_os_string();
+    backup.push(".bak");
+    assert_eq!(std::fs::read(&backup).unwrap(), original);
+}
+
+#[test]
+fn convert_writes_xml_to_stdout() {
+    let output = sfdl(&["convert", "tests/data/single_package_bulkfolder.xml"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let converted: SfdlFile = stdout.parse().unwrap();
+    assert_eq!(
+        converted,
+        SfdlFile::from_file("tests/data/single_package_bulkfolder.xml").unwrap()
+    );
+}
+
+#[test]
+fn convert_migrates_legacy_container() {
+    let output = sfdl(&["convert", "--migrate", "6", "tests/data/legacy_v3.xml"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let converted: SfdlFile = stdout.parse().unwrap();
+    assert_eq!(converted.sfdlfile_version, 6);
+    assert!(stdout.contains("<DataStaleDetection>true</DataStaleDetection>"));
+
+    let unsupported = sfdl(&["convert", "--migrate", "99", "tests/data/legacy_v3.xml"]);
+    assert_eq!(unsupported.status.code(), Some(9));
+}
+
+#[test]
+fn convert_to_json_and_back() {
+    let sample = "tests/data/filelist_mode.xml";
+    let json = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli-convert.json");
+    let json_str = json.to_str().unwrap();
+
+    let output = sfdl(&["convert", "--to", "json", sample, "-o", json_str]);
+    assert!(output.status.success());
+    let content = std::fs::read_to_string(&json).unwrap();
+    assert!(content.contains("\"package_name\": \"FileListPackage\""));
+
+    let output = sfdl(&["convert", json_str]);
+    assert!(output.status.success());
+    let converted: SfdlFile = String::from_utf8(output.stdout).unwrap().parse().unwrap();
+    assert_eq!(converted, SfdlFile::from_file(sample).unwrap());
+
+    let output = sfdl(&["convert", "--from", "json", "--to", "yaml", json_str]);
+    assert!(output.status.success());
+    let yaml = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        SfdlFile::from_yaml(&yaml).unwrap(),
+        SfdlFile::from_file(sample).unwrap()
+    );
+
+    let invalid = sfdl(&["convert", "--from", "toml", sample]);
+    assert_eq!(invalid.status.code(), Some(3));
+}
+
+#[test]
+fn validate_reports_diagnostics() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli-invalid.sfdl");
+    let content = std::fs::read_to_string("tests/data/single_package_bulkfolder.xml")
+        .unwrap()
+        .replace("<Port>21</Port>", "<Port>0</Port>");
+    std::fs::write(&path, content).unwrap();
+
+    let output = sfdl(&["validate", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(8));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("error [invalid-port] connection_info.port"));
+}
+
+#[test]
+fn validate_reports_schema_violations() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli-schema.sfdl");
+    let content = std::fs::read_to_string("tests/data/single_package_bulkfolder.xml")
+        .unwrap()
+        .replace("<Port>21</Port>", "<Port>ftp</Port>");
+    std::fs::write(&path, content).unwrap();
+
+    let output = sfdl(&["validate", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(8));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("error [invalid-value] /SFDLFile/ConnectionInfo/Port"));
+
+    let xml = std::fs::read_to_string("tests/data/single_package_bulkfolder.xml").unwrap();
+    std::fs::write(&path, &xml[..xml.rfind("</SFDLFile>").unwrap()]).unwrap();
+
+    let output = sfdl(&["validate", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(3));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("error [malformed-xml] /: unexpected end of document"));
+
+    // A well-formed document with the wrong root is reported, not parsed.
+    std::fs::write(&path, "<SFDLFiles><SFDLFile/></SFDLFiles>").unwrap();
+
+    let output = sfdl(&["validate", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(8));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("error [unexpected-element] /SFDLFiles"));
+}
+
+#[test]
+fn exit_code_reflects_error_variant() {
+    let missing = sfdl(&["info", "tests/data/does_not_exist.xml"]);
+    assert_eq!(missing.status.code(), Some(1));
+
+    let usage = sfdl(&["frobnicate"]);
+    assert_eq!(usage.status.code(), Some(2));
+
+    let parse = sfdl(&["validate", "Cargo.toml"]);
+    assert_eq!(parse.status.code(), Some(3));
+
+    let wrong_password = sfdl(&[
+        "validate",
+        "-p",
+        "wrong-password",
+        "tests/data/minimal_encrypted.xml",
+    ]);
+    assert_eq!(wrong_password.status.code(), Some(4));
+
+    let path = temp_copy("minimal_encrypted.xml", "already-encrypted");
+    let already = sfdl(&["encrypt", "-p", PASSWORD, path.to_str().unwrap()]);
+    assert_eq!(already.status.code(), Some(6));
+
+    let not_encrypted = sfdl(&[
+        "decrypt",
+        "-p",
+        PASSWORD,
+        "-o",
+        path.to_str().unwrap(),
+        "tests/data/single_package_bulkfolder.xml",
+    ]);
+    assert_eq!(not_encrypted.status.code(), Some(7));
+}
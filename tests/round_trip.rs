@@ -1,6 +1,6 @@
 #![allow(clippy::unwrap_used)]
 
-use sfdl::SfdlFile;
+use sfdl::{EncryptionState, SfdlError, SfdlFile};
 
 const PASSWORD: &str = "S3cr3tP4ssw0rd!";
 
@@ -72,3 +72,49 @@ fn ciphertext_generated_by_this_crate_decrypts_correctly() {
 
     assert_eq!(encrypted, expected);
 }
+
+#[test]
+fn decrypt_detected_ignores_wrong_flag() {
+    let expected = load_sample("mixed_mode.xml");
+    let mut sfdl = expected.clone();
+    sfdl.encrypt(PASSWORD).unwrap();
+    sfdl.encrypted = false;
+
+    assert!(matches!(
+        sfdl.decrypt(PASSWORD),
+        Err(SfdlError::NotEncrypted)
+    ));
+    assert_eq!(sfdl.detect_encryption(), EncryptionState::Encrypted);
+
+    sfdl.decrypt_detected(PASSWORD).unwrap();
+    assert_eq!(sfdl, expected);
+    assert!(matches!(
+        sfdl.decrypt_detected(PASSWORD),
+        Err(SfdlError::NotEncrypted)
+    ));
+}
+
+#[test]
+fn rekey_is_atomic() {
+    let expected = load_sample("filelist_mode.xml");
+    let mut sfdl = expected.clone();
+    sfdl.encrypt(PASSWORD).unwrap();
+    let encrypted = sfdl.clone();
+
+    assert!(matches!(
+        sfdl.rekey("wrong-password", "n3wP4ssw0rd"),
+        Err(SfdlError::Decrypt(_))
+    ));
+    assert_eq!(sfdl, encrypted);
+
+    sfdl.rekey(PASSWORD, "n3wP4ssw0rd").unwrap();
+    assert!(sfdl.encrypted);
+    assert!(!sfdl.verify_password(PASSWORD));
+    sfdl.decrypt("n3wP4ssw0rd").unwrap();
+    assert_eq!(sfdl, expected);
+
+    assert!(matches!(
+        sfdl.rekey("n3wP4ssw0rd", PASSWORD),
+        Err(SfdlError::NotEncrypted)
+    ));
+}